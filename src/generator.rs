@@ -0,0 +1,284 @@
+use crate::SlapQuadrant;
+use macaddr::MacAddr;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// A source of random bytes for generating MAC addresses. Only `fill_bytes` has to be
+/// implemented, the address shaping is shared by all generators.
+pub trait MacGenerator {
+    fn fill_bytes(&mut self, bytes: &mut [u8]);
+
+    /// Generates a random locally administered unicast MAC address. Only the multicast and
+    /// local bits are fixed, the other 46 bits are random.
+    fn random_mac(&mut self) -> MacAddr {
+        let mut mac = [0u8; 6];
+        self.fill_bytes(&mut mac);
+        mac[0] = (mac[0] & 0xFC) | 0x02;
+        MacAddr::from(mac)
+    }
+
+    /// Generates a random locally administered unicast MAC address in the given SLAP
+    /// quadrant. The quadrant fixes the low four bits of the first byte, the other 44 bits
+    /// are random.
+    fn random_mac_in_quadrant(&mut self, quadrant: SlapQuadrant) -> MacAddr {
+        let mut mac = [0u8; 6];
+        self.fill_bytes(&mut mac);
+        mac[0] = (mac[0] & 0xF0) | quadrant.bits();
+        MacAddr::from(mac)
+    }
+
+    /// Generates a random MAC address that keeps the given OUI (the first three bytes)
+    /// and only randomizes the NIC-specific lower three bytes.
+    fn random_mac_with_oui(&mut self, oui: [u8; 3]) -> MacAddr {
+        let mut mac = [0u8; 6];
+        self.fill_bytes(&mut mac[3..]);
+        mac[..3].copy_from_slice(&oui);
+        MacAddr::from(mac)
+    }
+
+    /// Generates a random MAC address inside the block identified by the `prefix_bits` most
+    /// significant bits of `prefix` (given right-aligned, e.g. `0x001B21` with 24 bits).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix_bits` is larger than 48.
+    fn random_mac_in_block(&mut self, prefix: u64, prefix_bits: u32) -> MacAddr {
+        assert!(
+            prefix_bits <= 48,
+            "a MAC address prefix can't be longer than 48 bits, got {}",
+            prefix_bits
+        );
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        let host_bits = 48 - prefix_bits;
+        let host_mask = (1u64 << host_bits) - 1;
+        let value = (prefix << host_bits) | (u64::from_be_bytes(bytes) & host_mask);
+
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&value.to_be_bytes()[2..]);
+        MacAddr::from(mac)
+    }
+}
+
+/// Takes the random bytes from the operating system's CSPRNG (`getrandom(2)` on Linux,
+/// `BCryptGenRandom` on Windows). This is the default generator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsGenerator;
+
+impl MacGenerator for OsGenerator {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        // Like rand's OsRng, there is no sensible way to continue without randomness
+        if let Err(e) = getrandom::getrandom(bytes) {
+            panic!(
+                "the operating system's random number generator failed: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Generates a reproducible sequence of addresses from a seed, using ChaCha20.
+#[derive(Debug, Clone)]
+pub struct SeededGenerator(ChaCha20Rng);
+
+impl SeededGenerator {
+    pub fn new(seed: u64) -> Self {
+        SeededGenerator(ChaCha20Rng::seed_from_u64(seed))
+    }
+}
+
+impl MacGenerator for SeededGenerator {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        self.0.fill_bytes(bytes);
+    }
+}
+
+/// Hands out the given bytes in order, starting over at the end, for tests. An empty
+/// sequence only produces zeros.
+#[derive(Debug, Clone)]
+pub struct SequenceGenerator {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl SequenceGenerator {
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        SequenceGenerator {
+            bytes: bytes.into(),
+            position: 0,
+        }
+    }
+
+    /// A sequence that produces the given addresses in turn, as far as the kind of address
+    /// being generated allows.
+    pub fn from_macs(macs: &[MacAddr]) -> Self {
+        SequenceGenerator::new(
+            macs.iter()
+                .flat_map(|mac| mac.as_bytes().to_vec())
+                .collect::<Vec<u8>>(),
+        )
+    }
+}
+
+impl MacGenerator for SequenceGenerator {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        if self.bytes.is_empty() {
+            bytes.fill(0);
+            return;
+        }
+        for byte in bytes {
+            *byte = self.bytes[self.position];
+            self.position = (self.position + 1) % self.bytes.len();
+        }
+    }
+}
+
+/// Lets a generator drive the helpers of the rand crate, like choosing from an iterator.
+#[cfg(feature = "oui")]
+pub(crate) struct GeneratorRng<'a>(pub &'a mut dyn MacGenerator);
+
+#[cfg(feature = "oui")]
+impl RngCore for GeneratorRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.0.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.0.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mac(bytes: [u8; 6]) -> MacAddr {
+        MacAddr::from(bytes)
+    }
+
+    #[test]
+    fn random_mac_is_local_unicast() {
+        let mut generator = SequenceGenerator::new([0xFF, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(
+            generator.random_mac(),
+            mac([0xFE, 0x11, 0x22, 0x33, 0x44, 0x55])
+        );
+        let mut generator = SequenceGenerator::new([0x01, 0xAB]);
+        assert_eq!(
+            generator.random_mac(),
+            mac([0x02, 0xAB, 0x01, 0xAB, 0x01, 0xAB])
+        );
+    }
+
+    #[test]
+    fn random_mac_in_quadrant_sets_low_nibble() {
+        let quadrants = [
+            (SlapQuadrant::Aai, 0xF2),
+            (SlapQuadrant::Eli, 0xFA),
+            (SlapQuadrant::Sai, 0xFE),
+            (SlapQuadrant::Reserved, 0xF6),
+        ];
+        for (quadrant, first) in quadrants {
+            let mut generator = SequenceGenerator::new([0xFF; 6]);
+            let mac = generator.random_mac_in_quadrant(quadrant);
+            assert_eq!(mac, MacAddr::from([first, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]));
+            assert_eq!(SlapQuadrant::of(mac), Some(quadrant));
+        }
+    }
+
+    #[test]
+    fn random_mac_with_oui_keeps_oui() {
+        let mut generator = SequenceGenerator::new([0xAA, 0xBB, 0xCC, 0xDD]);
+        assert_eq!(
+            generator.random_mac_with_oui([0x00, 0x1B, 0x21]),
+            mac([0x00, 0x1B, 0x21, 0xAA, 0xBB, 0xCC])
+        );
+        // Only the three NIC-specific bytes are taken from the generator
+        assert_eq!(
+            generator.random_mac_with_oui([0x00, 0x1B, 0x21]),
+            mac([0x00, 0x1B, 0x21, 0xDD, 0xAA, 0xBB])
+        );
+    }
+
+    #[test]
+    fn random_mac_in_block_keeps_prefix() {
+        let mut generator = SequenceGenerator::new([0xFF; 8]);
+        assert_eq!(
+            generator.random_mac_in_block(0x001B21, 24),
+            mac([0x00, 0x1B, 0x21, 0xFF, 0xFF, 0xFF])
+        );
+        assert_eq!(
+            generator.random_mac_in_block(0x70B3D51, 28),
+            mac([0x70, 0xB3, 0xD5, 0x1F, 0xFF, 0xFF])
+        );
+        assert_eq!(
+            generator.random_mac_in_block(0x70B3D5F5A, 36),
+            mac([0x70, 0xB3, 0xD5, 0xF5, 0xAF, 0xFF])
+        );
+        assert_eq!(
+            generator.random_mac_in_block(0x0200000000AB, 48),
+            mac([0x02, 0x00, 0x00, 0x00, 0x00, 0xAB])
+        );
+        let mut generator = SequenceGenerator::new([0x00; 8]);
+        assert_eq!(
+            generator.random_mac_in_block(0x70B3D51, 28),
+            mac([0x70, 0xB3, 0xD5, 0x10, 0x00, 0x00])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn random_mac_in_block_rejects_long_prefix() {
+        SequenceGenerator::new([]).random_mac_in_block(0, 49);
+    }
+
+    #[test]
+    fn from_macs_replays_addresses() {
+        let macs = [
+            mac([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]),
+            mac([0x02, 0x00, 0x00, 0x00, 0x00, 0x02]),
+        ];
+        let mut generator = SequenceGenerator::from_macs(&macs);
+        assert_eq!(generator.random_mac(), macs[0]);
+        assert_eq!(generator.random_mac(), macs[1]);
+        assert_eq!(generator.random_mac(), macs[0]);
+    }
+
+    #[test]
+    fn seeded_generator_is_reproducible() {
+        let generate = |seed| {
+            let mut generator = SeededGenerator::new(seed);
+            [
+                generator.random_mac(),
+                generator.random_mac_in_quadrant(SlapQuadrant::Eli),
+                generator.random_mac_with_oui([0x00, 0x1B, 0x21]),
+                generator.random_mac_in_block(0x70B3D51, 28),
+            ]
+        };
+        let macs = generate(42);
+        assert_eq!(
+            macs,
+            [
+                mac([0x7A, 0x48, 0xB5, 0xD7, 0x11, 0xBC]),
+                mac([0x9A, 0x63, 0x17, 0xA3, 0xF9, 0xC9]),
+                mac([0x00, 0x1B, 0x21, 0xD5, 0x67, 0x71]),
+                mac([0x70, 0xB3, 0xD5, 0x19, 0x39, 0xC9]),
+            ]
+        );
+        assert_eq!(macs, generate(42));
+        assert_ne!(macs, generate(43));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{MacchangerError, SlapQuadrant};
+use macaddr::MacAddr;
+use std::{fmt, str::FromStr};
+
+/// The maximum length of a hardware address, `MAX_ADDR_LEN` in the kernel.
+const MAX_HARDWARE_ADDRESS_LEN: usize = 32;
+
+/// The link-layer type of an interface, one of the kernel's ARPHRD_* values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinkType(pub u16);
+
+impl LinkType {
+    pub const ETHER: LinkType = LinkType(1);
+    pub const IEEE1394: LinkType = LinkType(24);
+    pub const EUI64: LinkType = LinkType(27);
+    pub const INFINIBAND: LinkType = LinkType(32);
+    pub const IEEE80211: LinkType = LinkType(801);
+    /// No known link type
+    pub const VOID: LinkType = LinkType(0xFFFF);
+
+    /// Guesses the link type from the length of an address, for addresses without one.
+    pub fn from_address_len(len: usize) -> Self {
+        match len {
+            6 => LinkType::ETHER,
+            8 => LinkType::EUI64,
+            16 => LinkType::IEEE1394,
+            20 => LinkType::INFINIBAND,
+            _ => LinkType::VOID,
+        }
+    }
+}
+
+impl fmt::Display for LinkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LinkType::ETHER => f.write_str("ether"),
+            LinkType::IEEE1394 => f.write_str("ieee1394"),
+            LinkType::EUI64 => f.write_str("eui64"),
+            LinkType::INFINIBAND => f.write_str("infiniband"),
+            LinkType::IEEE80211 => f.write_str("ieee802.11"),
+            LinkType::VOID => f.write_str("void"),
+            LinkType(link_type) => write!(f, "arphrd {}", link_type),
+        }
+    }
+}
+
+/// Unicast MAC address ranges that are set aside by IANA (RFC 7042) and must not be used as
+/// the address of an interface, by their first five bytes.
+const RESERVED_RANGES: &[([u8; 5], &str)] = &[
+    ([0x00, 0x00, 0x5E, 0x00, 0x00], "IANA (RFC 7042)"),
+    ([0x00, 0x00, 0x5E, 0x00, 0x53], "documentation (RFC 7042)"),
+];
+
+/// What kind of IEEE 802 address (EUI-48 or EUI-64) an address is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressClass {
+    /// A unicast address assigned by the vendor of the device
+    Universal,
+    /// A locally administered unicast address, with its SLAP quadrant for EUI-48 addresses
+    Local(Option<SlapQuadrant>),
+    Multicast,
+    Broadcast,
+    Zero,
+    /// A unicast address in a range reserved for the given purpose
+    Reserved(&'static str),
+}
+
+impl AddressClass {
+    /// Explains why the address can't be assigned to an interface.
+    pub fn problem(&self) -> Option<String> {
+        match self {
+            AddressClass::Universal | AddressClass::Local(_) => None,
+            AddressClass::Multicast => Some(
+                "the multicast bit (0x01 in the first byte) is set, which makes it a group address"
+                    .to_owned(),
+            ),
+            AddressClass::Broadcast => Some("it is the broadcast address".to_owned()),
+            AddressClass::Zero => Some("the all-zero address is not a valid address".to_owned()),
+            AddressClass::Reserved(purpose) => Some(format!("it is reserved for {}", purpose)),
+        }
+    }
+}
+
+impl fmt::Display for AddressClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressClass::Universal => f.write_str("universally administered unicast"),
+            AddressClass::Local(Some(quadrant)) => {
+                write!(f, "locally administered unicast (SLAP {})", quadrant)
+            }
+            AddressClass::Local(None) => f.write_str("locally administered unicast"),
+            AddressClass::Multicast => f.write_str("multicast"),
+            AddressClass::Broadcast => f.write_str("broadcast"),
+            AddressClass::Zero => f.write_str("all-zero"),
+            AddressClass::Reserved(purpose) => write!(f, "reserved for {}", purpose),
+        }
+    }
+}
+
+/// A link-layer address of any length, together with the type of link it belongs to. Besides
+/// 6-byte MAC addresses, this covers e.g. EUI-64 (8 bytes), IEEE 1394 (16 bytes) and
+/// InfiniBand (20 bytes) addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HardwareAddr {
+    link_type: LinkType,
+    bytes: Vec<u8>,
+}
+
+impl HardwareAddr {
+    pub fn new(link_type: LinkType, bytes: &[u8]) -> Result<Self, MacchangerError> {
+        if bytes.is_empty() || bytes.len() > MAX_HARDWARE_ADDRESS_LEN {
+            return Err(MacchangerError::InvalidHardwareAddress(format!(
+                "{} bytes long",
+                bytes.len()
+            )));
+        }
+        Ok(HardwareAddr {
+            link_type,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.bytes.iter().all(|b| *b == 0)
+    }
+
+    /// The address as a MAC address, for 6-byte (EUI-48) and 8-byte (EUI-64) addresses.
+    pub fn to_mac(&self) -> Option<MacAddr> {
+        match self.bytes.len() {
+            6 => Some(MacAddr::from(<[u8; 6]>::try_from(self.as_bytes()).ok()?)),
+            8 => Some(MacAddr::from(<[u8; 8]>::try_from(self.as_bytes()).ok()?)),
+            _ => None,
+        }
+    }
+
+    /// Classifies the address, for IEEE 802 (EUI-48 and EUI-64) addresses only.
+    pub fn class(&self) -> Option<AddressClass> {
+        let mac = self.to_mac()?;
+        let bytes = mac.as_bytes();
+        let class = if self.is_zero() {
+            AddressClass::Zero
+        } else if bytes.iter().all(|b| *b == 0xFF) {
+            AddressClass::Broadcast
+        } else if bytes[0] & 0x01 != 0 {
+            AddressClass::Multicast
+        } else if let Some((_, purpose)) = RESERVED_RANGES
+            .iter()
+            .find(|(prefix, _)| bytes.len() == 6 && bytes.starts_with(prefix))
+        {
+            AddressClass::Reserved(purpose)
+        } else if bytes[0] & 0x02 != 0 {
+            AddressClass::Local(SlapQuadrant::of(mac).filter(|_| bytes.len() == 6))
+        } else {
+            AddressClass::Universal
+        };
+        Some(class)
+    }
+
+    /// Parses an address in hex notation for a link of the given type.
+    pub fn parse(link_type: LinkType, s: &str) -> Result<Self, MacchangerError> {
+        let invalid = || MacchangerError::InvalidHardwareAddress(s.to_owned());
+        let bytes = s
+            .split([':', '-'])
+            .map(|byte| match byte.len() {
+                1 | 2 => u8::from_str_radix(byte, 16).map_err(|_| invalid()),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        HardwareAddr::new(link_type, &bytes).map_err(|_| invalid())
+    }
+}
+
+impl From<MacAddr> for HardwareAddr {
+    fn from(mac: MacAddr) -> Self {
+        HardwareAddr {
+            link_type: LinkType::from_address_len(mac.as_bytes().len()),
+            bytes: mac.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl FromStr for HardwareAddr {
+    type Err = MacchangerError;
+
+    /// Parses an address in hex notation, guessing the link type from its length.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let address = HardwareAddr::parse(LinkType::VOID, s)?;
+        Ok(HardwareAddr {
+            link_type: LinkType::from_address_len(address.len()),
+            ..address
+        })
+    }
+}
+
+impl fmt::Display for HardwareAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes the address in its usual hex notation, instead of as bytes.
+#[cfg(feature = "serde")]
+impl serde::Serialize for HardwareAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(s: &str) -> Option<AddressClass> {
+        s.parse::<HardwareAddr>().unwrap().class()
+    }
+
+    #[test]
+    fn classifies_special_addresses() {
+        assert_eq!(class("00:00:00:00:00:00"), Some(AddressClass::Zero));
+        assert_eq!(class("ff:ff:ff:ff:ff:ff"), Some(AddressClass::Broadcast));
+        assert_eq!(class("01:00:5e:00:00:01"), Some(AddressClass::Multicast));
+        assert_eq!(class("33:33:00:00:00:01"), Some(AddressClass::Multicast));
+        assert_eq!(class("03:00:00:00:00:01"), Some(AddressClass::Multicast));
+    }
+
+    #[test]
+    fn classifies_reserved_ranges() {
+        assert_eq!(
+            class("00:00:5e:00:00:12"),
+            Some(AddressClass::Reserved("IANA (RFC 7042)"))
+        );
+        assert_eq!(
+            class("00:00:5e:00:53:01"),
+            Some(AddressClass::Reserved("documentation (RFC 7042)"))
+        );
+        // VRRP virtual router addresses are assigned to interfaces by design
+        assert_eq!(class("00:00:5e:00:01:01"), Some(AddressClass::Universal));
+        assert_eq!(class("00:00:5e:00:02:01"), Some(AddressClass::Universal));
+    }
+
+    #[test]
+    fn classifies_unicast_addresses() {
+        assert_eq!(class("00:1b:21:12:34:56"), Some(AddressClass::Universal));
+        let quadrants = [
+            ("02:00:00:00:00:01", SlapQuadrant::Aai),
+            ("0a:00:00:00:00:01", SlapQuadrant::Eli),
+            ("0e:00:00:00:00:01", SlapQuadrant::Sai),
+            ("06:00:00:00:00:01", SlapQuadrant::Reserved),
+        ];
+        for (address, quadrant) in quadrants {
+            assert_eq!(class(address), Some(AddressClass::Local(Some(quadrant))));
+        }
+    }
+
+    #[test]
+    fn classifies_eui64_addresses() {
+        assert_eq!(
+            class("00:1b:21:ff:fe:12:34:56"),
+            Some(AddressClass::Universal)
+        );
+        // SLAP quadrants are only defined for EUI-48 addresses
+        assert_eq!(
+            class("02:1b:21:ff:fe:12:34:56"),
+            Some(AddressClass::Local(None))
+        );
+        assert_eq!(class("00:00:00:00:00:00:00:00"), Some(AddressClass::Zero));
+        assert_eq!(
+            class("ff:ff:ff:ff:ff:ff:ff:ff"),
+            Some(AddressClass::Broadcast)
+        );
+        assert_eq!(
+            class("01:1b:21:ff:fe:12:34:56"),
+            Some(AddressClass::Multicast)
+        );
+        // The IANA ranges are EUI-48 prefixes
+        assert_eq!(
+            class("00:00:5e:00:53:00:00:01"),
+            Some(AddressClass::Universal)
+        );
+    }
+
+    #[test]
+    fn only_classifies_ieee802_addresses() {
+        assert_eq!(class("00:01:02:03"), None);
+        assert_eq!(
+            class("80:00:02:08:fe:80:00:00:00:00:00:00:00:02:c9:03:00:a1:b2:c3"),
+            None
+        );
+    }
+}
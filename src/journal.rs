@@ -0,0 +1,190 @@
+use crate::{os::LinuxMacchangerError, HardwareAddr, LinkType};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{ErrorKind, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_STATE_DIR: &str = "/var/lib";
+const JOURNAL_DIR: &str = "macchanger";
+const JOURNAL_FILE: &str = "journal";
+
+/// Persistent record of the MAC address every interface had before macchanger first changed
+/// it, so it can be restored even when the driver doesn't report a permanent address.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    entries: BTreeMap<String, HardwareAddr>,
+}
+
+impl Journal {
+    /// Opens the journal in `/var/lib/macchanger`. `MACCHANGER_STATE_DIR` replaces `/var/lib`,
+    /// the journal always lives in a `macchanger` directory of its own inside it.
+    pub fn open() -> Result<Self, LinuxMacchangerError> {
+        let state_dir = env::var_os("MACCHANGER_STATE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or(PathBuf::from(DEFAULT_STATE_DIR));
+        Self::open_in(&state_dir)
+    }
+
+    fn open_in(state_dir: &Path) -> Result<Self, LinuxMacchangerError> {
+        let path = state_dir.join(JOURNAL_DIR).join(JOURNAL_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(journal_error(&path, e)),
+        };
+        Ok(Journal {
+            path,
+            entries: parse(&contents),
+        })
+    }
+
+    /// The MAC address the interface had before it was first changed.
+    pub fn original_mac(&self, interface: &str) -> Option<&HardwareAddr> {
+        self.entries.get(interface)
+    }
+
+    /// Records the original MAC address of the interface, unless one is recorded already.
+    /// Returns whether the address was recorded.
+    pub fn record(
+        &mut self,
+        interface: &str,
+        mac: &HardwareAddr,
+    ) -> Result<bool, LinuxMacchangerError> {
+        if self.entries.contains_key(interface) {
+            return Ok(false);
+        }
+        self.entries.insert(interface.to_owned(), mac.clone());
+        self.save().map(|_| true)
+    }
+
+    /// Forgets the interface, when its recorded MAC address turned out not to be needed.
+    pub fn remove(&mut self, interface: &str) -> Result<(), LinuxMacchangerError> {
+        if self.entries.remove(interface).is_none() {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// Writes the journal to a temporary file which then atomically replaces the journal, so
+    /// a crash never leaves a truncated journal behind. Only root can read or write it.
+    fn save(&self) -> Result<(), LinuxMacchangerError> {
+        // Missing parents are created like `mkdir -p` would. Only our own directory is
+        // restricted, an existing one is left as the admin set it up.
+        let state_dir = self.path.parent().unwrap_or(&self.path);
+        if let Some(parent) = state_dir.parent() {
+            fs::create_dir_all(parent).map_err(|e| journal_error(parent, e))?;
+        }
+        match DirBuilder::new().mode(0o700).create(state_dir) {
+            Err(e) if e.kind() != ErrorKind::AlreadyExists => {
+                return Err(journal_error(state_dir, e))
+            }
+            _ => {}
+        }
+
+        let mut contents =
+            String::from("# Original MAC addresses of the interfaces changed by macchanger\n");
+        for (interface, mac) in &self.entries {
+            contents.push_str(&format!("{} {} {}\n", interface, mac, mac.link_type().0));
+        }
+
+        let temp_path = self.path.with_extension("tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp_path)
+            .map_err(|e| journal_error(&temp_path, e))?;
+        file.write_all(contents.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| journal_error(&temp_path, e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| journal_error(&self.path, e))?;
+
+        // Make sure the rename itself is persisted
+        fs::File::open(state_dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| journal_error(state_dir, e))
+    }
+}
+
+/// Parses the `<interface> <address> <link type>` lines of the journal, skipping comments and
+/// lines that can't be parsed.
+fn parse(contents: &str) -> BTreeMap<String, HardwareAddr> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let interface = fields.next()?;
+            let address = fields.next()?;
+            let link_type = LinkType(fields.next()?.parse().ok()?);
+            let address = HardwareAddr::parse(link_type, address).ok()?;
+            Some((interface.to_owned(), address))
+        })
+        .collect()
+}
+
+fn journal_error(path: &Path, error: std::io::Error) -> LinuxMacchangerError {
+    LinuxMacchangerError::Journal(format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "macchanger-journal-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn parses_entries() {
+        let entries = parse(
+            "# comment\n\
+             eth0 00:11:22:33:44:55 1\n\
+             ib0 80:00:00:48:fe:80:00:00:00:00:00:00:00:02:c9:03:00:01:02:03 32\n\
+             wlan0 00:11:22:33:44:66\n\
+             eth1 not-a-mac 1\n",
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["eth0"].to_string(), "00:11:22:33:44:55");
+        assert_eq!(entries["eth0"].link_type(), LinkType::ETHER);
+        assert_eq!(entries["ib0"].link_type(), LinkType::INFINIBAND);
+        assert_eq!(entries["ib0"].len(), 20);
+    }
+
+    #[test]
+    fn saves_and_reopens() {
+        // Neither the state dir nor its parent exist yet
+        let root = temp_dir("save");
+        let state_dir = root.join("state");
+        let mac: HardwareAddr = "00:11:22:33:44:55".parse().unwrap();
+
+        let mut journal = Journal::open_in(&state_dir).unwrap();
+        assert!(journal.record("eth0", &mac).unwrap());
+        assert!(!journal
+            .record("eth0", &"00:11:22:33:44:66".parse().unwrap())
+            .unwrap());
+        assert!(journal.record("eth1", &mac).unwrap());
+        journal.remove("eth1").unwrap();
+
+        let reopened = Journal::open_in(&state_dir).unwrap();
+        let contents = fs::read_to_string(state_dir.join(JOURNAL_DIR).join(JOURNAL_FILE));
+        let mode = fs::metadata(state_dir.join(JOURNAL_DIR)).map(|m| m.permissions().mode());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(reopened.original_mac("eth0"), Some(&mac));
+        assert_eq!(reopened.original_mac("eth1"), None);
+        assert!(contents.unwrap().ends_with("\neth0 00:11:22:33:44:55 1\n"));
+        assert_eq!(mode.unwrap() & 0o777, 0o700);
+    }
+}
@@ -2,18 +2,47 @@
 #[path = "windows.rs"]
 mod os;
 
+mod generator;
+mod hwaddr;
+mod pattern;
 mod util;
 
+#[cfg(feature = "oui")]
+mod oui;
+
 #[cfg(target_os = "linux")]
 #[path = "linux.rs"]
 mod os;
 
+#[cfg(target_os = "linux")]
+mod netlink;
+
+#[cfg(target_os = "linux")]
+mod journal;
+
+#[cfg(target_os = "linux")]
+mod probe;
+
+pub use generator::{MacGenerator, OsGenerator, SeededGenerator, SequenceGenerator};
+pub use hwaddr::{AddressClass, HardwareAddr, LinkType};
 use macaddr::MacAddr;
 #[cfg(target_os = "linux")]
 use os::LinuxMacchangerError;
-use os::{change_mac, get_hardware_mac, list_adapters, list_interfaces};
+use os::{
+    change_mac, get_adapter_kind, get_hardware_mac, get_interface, list_adapters, list_interfaces,
+    list_neighbor_addresses,
+};
+#[cfg(feature = "oui")]
+pub use oui::{
+    import_oui_database, lookup_vendor, registry as oui_registry, BlockSize, OuiRegistry, Vendor,
+};
+pub use pattern::MacPattern;
+use std::{collections::HashSet, fmt};
 use thiserror::Error;
-pub use util::generate_random_mac;
+pub use util::{
+    generate_random_mac, generate_random_mac_in_block, generate_random_mac_in_quadrant,
+    generate_random_mac_with_oui,
+};
 
 #[derive(Error, Debug)]
 pub enum MacchangerError {
@@ -33,25 +62,368 @@ pub enum MacchangerError {
     AdapterError,
     #[error("Something went wrong when retrieving the interface list")]
     ListInterfacesError,
+    #[error("Interface {name} not found{}", match .candidates.as_slice() {
+        [] => String::new(),
+        candidates => format!(", did you mean: {}?", candidates.join(", ")),
+    })]
+    InterfaceNotFound {
+        name: String,
+        candidates: Vec<String>,
+    },
+    #[error("Interface {0} exists but has no link-layer (MAC) address")]
+    NoLinkLayerAddress(String),
+    #[error("The MAC address was not applied: requested {requested}, but the interface reports {actual}")]
+    VerificationFailed {
+        requested: HardwareAddr,
+        actual: HardwareAddr,
+    },
+    #[error("Invalid hardware address: {0}")]
+    InvalidHardwareAddress(String),
+    #[error("Invalid MAC address pattern: {0}")]
+    InvalidMacPattern(String),
+    #[error("The hardware address {0} is not a MAC address")]
+    NotAMacAddress(HardwareAddr),
+    #[error("The change options contradict each other: {0}")]
+    ConflictingOptions(String),
+    #[error("{error} (rollback {})", match .rollback_error {
+        None => "succeeded, the original MAC address and link state were restored".to_owned(),
+        Some(e) => format!("failed: {e}"),
+    })]
+    ChangeRolledBack {
+        error: Box<MacchangerError>,
+        rollback_error: Option<Box<MacchangerError>>,
+    },
+    #[error("No permanent MAC address found for interface {interface} ({})", .attempts.join(", "))]
+    NoPermanentMac {
+        interface: String,
+        /// Why each source failed, in the order they were tried
+        attempts: Vec<String>,
+    },
+    #[error("The address {address} can't be assigned to an interface: {problem} (force the change to use it anyway)")]
+    UnassignableAddress {
+        address: HardwareAddr,
+        problem: String,
+    },
+    #[error("The MAC address {0} is already in use by another host on the network")]
+    AddressInUse(HardwareAddr),
+    #[error("Could not generate a MAC address that is not in use yet after {0} attempts")]
+    NoUnusedMac(u32),
+    #[error("No vendor in the OUI registry matches the requested kind of adapter")]
+    NoVendorAvailable,
+    #[error("Something went wrong when importing the OUI database: {0}")]
+    OuiImportError(String),
+    #[error("Something went wrong with the cached OUI index: {0}")]
+    OuiCacheError(String),
+    #[error("The vendor search pattern is invalid: {0}")]
+    InvalidSearchPattern(String),
     #[cfg(target_os = "linux")]
     #[error("Something went wrong with the Linux code: {0}")]
     #[cfg(target_os = "linux")]
     LinuxError(LinuxMacchangerError),
 }
 
+/// Options controlling how a MAC address change is applied.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeOptions {
+    /// Leave the interface down after changing its MAC address, instead of restoring the
+    /// up/down state it had before the change. Can't be combined with `LinkDownPolicy::Never`.
+    pub keep_down: bool,
+    pub link_down: LinkDownPolicy,
+    /// Send ARP probes from the new address before adopting it, and refuse the change when
+    /// another host on the link already uses it. The probes ask for the known neighbors and
+    /// gateways of the link, a host that is none of them is only found if it sends traffic
+    /// while probing.
+    pub probe: bool,
+    /// Change to addresses that can't be assigned to an interface (e.g. multicast ones) too
+    pub force: bool,
+}
+
+/// Whether the link is taken down while its MAC address is changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkDownPolicy {
+    /// Try to change the address while the link is up, and only take it down when the
+    /// driver refuses that
+    #[default]
+    Auto,
+    /// Never take the link down, fail when the driver doesn't allow a live change
+    Never,
+    /// Always take the link down during the change
+    Always,
+}
+
+/// Options controlling how random MAC addresses are generated.
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    /// The IEEE 802c SLAP quadrant the address should fall into. Without one, any locally
+    /// administered unicast address can be generated.
+    pub quadrant: Option<SlapQuadrant>,
+    /// The prefix or template the address has to match. The bits it doesn't fix are still
+    /// chosen so that the address is a locally administered unicast address. Where both fix
+    /// the same bits, the pattern wins over the quadrant.
+    pub pattern: Option<MacPattern>,
+}
+
+/// The quadrants of the IEEE 802c Structured Local Address Plan, which split the locally
+/// administered address space by the Y and Z bits of the first byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SlapQuadrant {
+    /// Administratively Assigned Identifier (`x2`)
+    Aai,
+    /// Extended Local Identifier (`xA`), derived from a Company ID
+    Eli,
+    /// Standard Assigned Identifier (`xE`), assigned by a protocol
+    Sai,
+    /// Reserved for future use by administrators (`x6`)
+    Reserved,
+}
+
+impl SlapQuadrant {
+    /// The low four bits (Z, Y, local and multicast bit) of the first byte of addresses in
+    /// this quadrant.
+    pub fn bits(self) -> u8 {
+        match self {
+            SlapQuadrant::Aai => 0x2,
+            SlapQuadrant::Eli => 0xA,
+            SlapQuadrant::Sai => 0xE,
+            SlapQuadrant::Reserved => 0x6,
+        }
+    }
+
+    /// The quadrant of a locally administered unicast address, `None` for other addresses.
+    pub fn of(mac: MacAddr) -> Option<Self> {
+        match mac.as_bytes()[0] & 0x0F {
+            0x2 => Some(SlapQuadrant::Aai),
+            0xA => Some(SlapQuadrant::Eli),
+            0xE => Some(SlapQuadrant::Sai),
+            0x6 => Some(SlapQuadrant::Reserved),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SlapQuadrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlapQuadrant::Aai => f.write_str("AAI"),
+            SlapQuadrant::Eli => f.write_str("ELI"),
+            SlapQuadrant::Sai => f.write_str("SAI"),
+            SlapQuadrant::Reserved => f.write_str("reserved"),
+        }
+    }
+}
+
 pub fn do_change_mac(mac: MacAddr, interface: String) -> Result<MacAddr, MacchangerError> {
-    change_mac(mac, interface)
+    do_change_mac_with_options(mac, interface, &ChangeOptions::default())
+}
+
+pub fn do_change_mac_with_options(
+    mac: MacAddr,
+    interface: String,
+    options: &ChangeOptions,
+) -> Result<MacAddr, MacchangerError> {
+    let address = do_change_hardware_address(&mac.into(), interface, options)?;
+    address
+        .to_mac()
+        .ok_or(MacchangerError::NotAMacAddress(address))
+}
+
+/// Changes the hardware address of the interface to an address of any length, such as the
+/// 20-byte address of an InfiniBand interface.
+///
+/// On Linux the original address is recorded in a journal before the first change, so it can
+/// always be restored. If the journal can't be written (e.g. `/var/lib` is read-only), the
+/// change is refused rather than losing the original address. Point `MACCHANGER_STATE_DIR` to
+/// a writable location in that case, missing directories are created.
+pub fn do_change_hardware_address(
+    address: &HardwareAddr,
+    interface: String,
+    options: &ChangeOptions,
+) -> Result<HardwareAddr, MacchangerError> {
+    if options.keep_down && options.link_down == LinkDownPolicy::Never {
+        return Err(MacchangerError::ConflictingOptions(
+            "the link can't be kept down when it may never be taken down".to_owned(),
+        ));
+    }
+    if !options.force {
+        validate_address(address)?;
+    }
+    change_mac(address, interface, options)
+}
+
+/// Rejects IEEE 802 addresses that can't be assigned to an interface, like multicast or
+/// reserved ones. Addresses of other link types are passed on to the OS as they are.
+pub fn validate_address(address: &HardwareAddr) -> Result<(), MacchangerError> {
+    match address.class().and_then(|class| class.problem()) {
+        Some(problem) => Err(MacchangerError::UnassignableAddress {
+            address: address.clone(),
+            problem,
+        }),
+        None => Ok(()),
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Interface {
     pub name: String,
-    pub mac: MacAddr,
+    pub mac: HardwareAddr,
+    /// The interface index used by the kernel
+    pub index: u32,
+    /// The IFF_* flags of the interface
+    pub flags: u32,
+    pub operstate: OperState,
+    /// Whether the link has carrier, unknown while the interface is down
+    pub carrier: Option<bool>,
+    pub mtu: u32,
+    /// The adapter (device) behind the interface
+    pub adapter: Adapter,
+    /// How the current MAC address was assigned
+    pub origin: AddressOrigin,
+    /// The permanent (or original) MAC address, if it is known
+    pub permanent_mac: Option<HardwareAddr>,
+    /// Problems that came up while gathering the details of this interface
+    pub warnings: Vec<String>,
+}
+
+impl Interface {
+    /// Whether the interface runs with a changed MAC address: one that differs from its
+    /// permanent MAC address or, when that is unknown, one that was set from userspace.
+    pub fn is_spoofed(&self) -> bool {
+        match &self.permanent_mac {
+            Some(permanent_mac) => *permanent_mac != self.mac,
+            None => self.origin == AddressOrigin::Set,
+        }
+    }
+}
+
+/// How the kernel assigned the current MAC address of an interface, see `addr_assign_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AddressOrigin {
+    /// The address burned into the device
+    Permanent,
+    /// A randomly generated address
+    Random,
+    /// An address taken from another device, e.g. by a bond or bridge
+    Stolen,
+    /// An address set from userspace
+    Set,
+    Unknown,
+}
+
+impl fmt::Display for AddressOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AddressOrigin::Permanent => "permanent",
+            AddressOrigin::Random => "random",
+            AddressOrigin::Stolen => "stolen",
+            AddressOrigin::Set => "set",
+            AddressOrigin::Unknown => "unknown",
+        })
+    }
+}
+
+/// The operational state of an interface (RFC 2863), see `operstate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OperState {
+    Up,
+    Down,
+    Dormant,
+    LowerLayerDown,
+    NotPresent,
+    Testing,
+    Unknown,
+}
+
+impl fmt::Display for OperState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OperState::Up => "up",
+            OperState::Down => "down",
+            OperState::Dormant => "dormant",
+            OperState::LowerLayerDown => "lowerlayerdown",
+            OperState::NotPresent => "notpresent",
+            OperState::Testing => "testing",
+            OperState::Unknown => "unknown",
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Adapter {
     pub name: String,
+    pub kind: AdapterKind,
+    /// The kernel driver bound to the device
+    pub driver: Option<String>,
+    /// Where the device sits in the device tree, e.g. `pci0000:00/0000:00:1f.6`
+    pub bus_path: Option<String>,
+    /// The vendor and device IDs of PCI and USB devices
+    pub ids: Option<DeviceIds>,
+    /// Problems that came up while identifying this adapter
+    pub warnings: Vec<String>,
+}
+
+/// The class of device a network adapter belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AdapterKind {
+    Wired,
+    Wireless,
+    Virtual,
+}
+
+impl fmt::Display for AdapterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AdapterKind::Wired => "wired",
+            AdapterKind::Wireless => "wireless",
+            AdapterKind::Virtual => "virtual",
+        })
+    }
+}
+
+/// The IDs a PCI or USB device identifies itself with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceIds {
+    pub bus: DeviceBus,
+    pub vendor_id: u16,
+    /// The device ID of a PCI device, or the product ID of a USB device
+    pub device_id: u16,
+}
+
+impl fmt::Display for DeviceIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:04x}:{:04x}",
+            self.bus, self.vendor_id, self.device_id
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DeviceBus {
+    Pci,
+    Usb,
+}
+
+impl fmt::Display for DeviceBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeviceBus::Pci => "PCI",
+            DeviceBus::Usb => "USB",
+        })
+    }
 }
 
 pub fn retrieve_interfaces() -> Result<Vec<Interface>, MacchangerError> {
@@ -59,11 +431,171 @@ pub fn retrieve_interfaces() -> Result<Vec<Interface>, MacchangerError> {
     Ok(interfaces)
 }
 
+pub fn retrieve_interface(interface: String) -> Result<Interface, MacchangerError> {
+    Ok(get_interface(interface)?.into())
+}
+
 pub fn retrieve_adapters() -> Result<Vec<Adapter>, MacchangerError> {
     let adapters = list_adapters()?.into_iter().map(|a| a.into()).collect();
     Ok(adapters)
 }
 
+/// The source a permanent (original) MAC address was retrieved from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum MacSource {
+    /// The `ETHTOOL_GPERMADDR` ioctl
+    Ethtool,
+    /// The `IFLA_PERM_ADDRESS` netlink attribute
+    Netlink,
+    /// The current address in sysfs, which the kernel marks as permanent
+    Sysfs,
+    /// The address recorded before macchanger first changed the interface
+    Journal,
+    /// The `OriginalNetworkAddress` value in the Windows registry
+    Registry,
+}
+
+impl fmt::Display for MacSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MacSource::Ethtool => "ethtool",
+            MacSource::Netlink => "netlink",
+            MacSource::Sysfs => "sysfs",
+            MacSource::Journal => "journal",
+            MacSource::Registry => "registry",
+        })
+    }
+}
+
+/// A permanent (original) MAC address together with the source it was found in.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HardwareMac {
+    pub mac: HardwareAddr,
+    pub source: MacSource,
+}
+
 pub fn retrieve_hardware_mac(interface: String) -> Result<MacAddr, MacchangerError> {
+    let address = discover_hardware_mac(interface)?.mac;
+    address
+        .to_mac()
+        .ok_or(MacchangerError::NotAMacAddress(address))
+}
+
+/// Retrieves the permanent MAC address of the interface, trying every source the platform
+/// offers, and reports which source answered.
+pub fn discover_hardware_mac(interface: String) -> Result<HardwareMac, MacchangerError> {
     get_hardware_mac(interface)
 }
+
+pub fn retrieve_adapter_kind(interface: String) -> Result<AdapterKind, MacchangerError> {
+    get_adapter_kind(interface)
+}
+
+/// Generates a random locally administered MAC address according to the options.
+pub fn generate_random_mac_with_options(options: &GenerateOptions) -> MacAddr {
+    generate_mac(&mut OsGenerator, options)
+}
+
+/// Generates a locally administered MAC address according to the options, with the random
+/// bytes taken from the given generator.
+pub fn generate_mac(generator: &mut dyn MacGenerator, options: &GenerateOptions) -> MacAddr {
+    let mac = match options.quadrant {
+        Some(quadrant) => generator.random_mac_in_quadrant(quadrant),
+        None => generator.random_mac(),
+    };
+    match (options.pattern, mac) {
+        (Some(pattern), MacAddr::V6(mac)) => MacAddr::V6(pattern.apply(mac)),
+        (_, mac) => mac,
+    }
+}
+
+/// How often `generate_unused_mac` generates a new address before giving up.
+const MAX_GENERATE_ATTEMPTS: u32 = 64;
+
+/// A generated MAC address that is not in use by a local interface or a known neighbor.
+#[derive(Debug, Clone, Copy)]
+pub struct GeneratedMac {
+    pub mac: MacAddr,
+    /// How many generated addresses were discarded because they were in use already
+    pub retries: u32,
+}
+
+/// Calls `generate` until it returns an address that neither a local interface nor a
+/// neighbor in the ARP/NDP table uses.
+pub fn generate_unused_mac(
+    mut generate: impl FnMut() -> Result<MacAddr, MacchangerError>,
+) -> Result<GeneratedMac, MacchangerError> {
+    let in_use = addresses_in_use()?;
+    for retries in 0..MAX_GENERATE_ATTEMPTS {
+        let mac = generate()?;
+        if !in_use.contains(mac.as_bytes()) {
+            return Ok(GeneratedMac { mac, retries });
+        }
+    }
+    Err(MacchangerError::NoUnusedMac(MAX_GENERATE_ATTEMPTS))
+}
+
+/// The current and permanent addresses of the local interfaces and the addresses of the
+/// known neighbors. Only the bytes are compared, since neighbors don't have a link type.
+fn addresses_in_use() -> Result<HashSet<Vec<u8>>, MacchangerError> {
+    let mut in_use = HashSet::new();
+    for interface in retrieve_interfaces()? {
+        in_use.insert(interface.mac.as_bytes().to_vec());
+        if let Some(permanent_mac) = interface.permanent_mac {
+            in_use.insert(permanent_mac.as_bytes().to_vec());
+        }
+    }
+    for address in list_neighbor_addresses()? {
+        in_use.insert(address.as_bytes().to_vec());
+    }
+    Ok(in_use)
+}
+
+/// Generates a random MAC address for the given interface that keeps the vendor (OUI) bytes
+/// of its permanent MAC address, falling back to the current one when the permanent address
+/// cannot be retrieved.
+pub fn generate_vendor_preserving_mac(
+    interface: String,
+    generator: &mut dyn MacGenerator,
+) -> Result<MacAddr, MacchangerError> {
+    let base_mac = match retrieve_hardware_mac(interface.clone()) {
+        Ok(mac) if !mac.as_bytes().iter().all(|b| *b == 0) => mac,
+        hardware_mac => retrieve_interfaces()?
+            .into_iter()
+            .find(|i| i.name == interface)
+            .and_then(|i| i.mac.to_mac())
+            .ok_or_else(|| hardware_mac.err().unwrap_or(MacchangerError::Generic))?,
+    };
+
+    let mut oui = [0u8; 3];
+    oui.copy_from_slice(&base_mac.as_bytes()[..3]);
+    Ok(generator.random_mac_with_oui(oui))
+}
+
+/// Generates a random MAC address inside the assignment of a randomly chosen real vendor.
+/// When `kind` is given, only vendors making that kind of adapter are considered.
+#[cfg(feature = "oui")]
+pub fn generate_random_vendor_mac(
+    kind: Option<AdapterKind>,
+    generator: &mut dyn MacGenerator,
+) -> Result<MacAddr, MacchangerError> {
+    let vendor = oui::registry()?
+        .random_vendor(kind, generator)
+        .ok_or(MacchangerError::NoVendorAvailable)?;
+    Ok(generator.random_mac_in_block(vendor.prefix, vendor.block.prefix_bits()))
+}
+
+/// Generates a random MAC address from a real vendor making the same kind of adapter
+/// (e.g. wireless or wired) as the one behind the given interface. The kind of a vendor is
+/// guessed from its name, so this is only a coarse approximation of the adapter kind.
+#[cfg(feature = "oui")]
+pub fn generate_same_kind_mac(
+    interface: String,
+    generator: &mut dyn MacGenerator,
+) -> Result<MacAddr, MacchangerError> {
+    let kind = retrieve_adapter_kind(interface)?;
+    generate_random_vendor_mac(Some(kind), generator)
+}
@@ -1,41 +1,299 @@
-use crate::{Adapter, Interface, MacchangerError};
-use macaddr::MacAddr;
+use crate::{
+    journal::Journal, netlink::NetlinkSocket, probe::ArpProber, util::similar_names, Adapter,
+    AdapterKind, AddressOrigin, ChangeOptions, DeviceBus, DeviceIds, HardwareAddr, HardwareMac,
+    Interface, LinkDownPolicy, LinkType, MacSource, MacchangerError, OperState,
+};
 use nix::{
     errno::Errno,
     ifaddrs::{getifaddrs, InterfaceAddress},
     ioctl_read_bad, ioctl_readwrite_bad,
     libc::{
-        sockaddr, ARPHRD_ETHER, IFF_UP, IF_NAMESIZE, SIOCETHTOOL, SIOCGIFFLAGS, SIOCSIFFLAGS,
-        SIOCSIFHWADDR,
+        sockaddr, IFF_UP, IF_NAMESIZE, NUD_FAILED, RTF_GATEWAY, SIOCETHTOOL, SIOCGIFFLAGS,
+        SIOCSIFFLAGS, SIOCSIFHWADDR,
     },
+    net::if_::if_nametoindex,
     sys::socket::{socket, SockFlag},
 };
 use pci_ids::Device;
 use std::{
     fs,
     io::Write,
-    ops::ControlFlow,
+    net::{IpAddr, Ipv4Addr},
     os::fd::{AsRawFd, OwnedFd},
     path::Path,
 };
 use thiserror::Error;
 
-pub fn change_mac(mac: MacAddr, interface: String) -> Result<MacAddr, MacchangerError> {
-    let interfaces = list_interfaces()?;
-    let temp_interface = interfaces
-        .into_iter()
-        .filter(|i| i.name == interface)
-        .collect::<Vec<LinuxInterface>>();
-    let interface = temp_interface.first().unwrap();
+pub fn change_mac(
+    address: &HardwareAddr,
+    interface: String,
+    options: &ChangeOptions,
+) -> Result<HardwareAddr, MacchangerError> {
+    let interface = &find_interface(&interface)?;
+    // The address is meant for this link, whatever type it was parsed as
+    let mac = &HardwareAddr::new(interface.adapter.mac.link_type(), address.as_bytes())?;
+    if options.probe && probe_mac(interface, mac)? {
+        return Err(MacchangerError::AddressInUse(mac.clone()));
+    }
+    let snapshot = LinkSnapshot {
+        mac: get_current_mac(interface)?,
+        up: get_interface_active(interface)?,
+    };
+
+    // Remember the address from before our first change, so it can always be restored. The
+    // entry stays after a restore, since the kernel then reports the address as set and only
+    // the journal still knows it is the original one.
+    let mut journal = Journal::open()?;
+    let recorded = journal.record(&interface.name, &snapshot.mac)?;
+
+    match apply_mac(interface, mac, options).and_then(|_| verify_mac(interface, mac)) {
+        Ok(actual) => Ok(actual),
+        Err(error) => {
+            let rollback = rollback(interface, &snapshot);
+            // Nothing changed after all, so the entry recorded for this change isn't needed.
+            // The change error is more relevant than a failure to clean up the journal.
+            if rollback.is_ok() && recorded {
+                let _ = journal.remove(&interface.name);
+            }
+            match rollback {
+                // The change failed before it touched the interface
+                Ok(false) => Err(error),
+                Ok(true) => Err(MacchangerError::ChangeRolledBack {
+                    error: Box::new(error),
+                    rollback_error: None,
+                }),
+                Err(rollback_error) => Err(MacchangerError::ChangeRolledBack {
+                    error: Box::new(error),
+                    rollback_error: Some(Box::new(rollback_error)),
+                }),
+            }
+        }
+    }
+}
+
+/// Probes the link for another host using the address. Only Ethernet links can be probed,
+/// since the probes are ARP packets. The probes ask for the IPv4 addresses of the known
+/// neighbors and gateways on the link, which the other host answers if it is one of them.
+fn probe_mac(interface: &LinuxInterface, mac: &HardwareAddr) -> Result<bool, MacchangerError> {
+    let mac_bytes: [u8; 6] = match mac.as_bytes().try_into() {
+        Ok(bytes) if mac.link_type() == LinkType::ETHER => bytes,
+        _ => return Err(LinuxMacchangerError::ProbeUnsupported(mac.link_type()).into()),
+    };
+    // Nothing can be sent on a link that is down
+    if !get_interface_active(interface)? {
+        return Err(LinuxMacchangerError::ProbeLinkDown(interface.name.clone()).into());
+    }
+    let index = get_interface_index(interface)?;
+    let prober = ArpProber::open(index)?;
+    Ok(prober.probe(mac_bytes, &probe_targets(interface, index)?)?)
+}
+
+/// The IPv4 addresses on the link that hosts answer ARP requests for: the neighbors and
+/// default gateways of the interface, and its own addresses to catch address conflicts.
+fn probe_targets(interface: &LinuxInterface, index: u32) -> Result<Vec<Ipv4Addr>, MacchangerError> {
+    let mut targets: Vec<Ipv4Addr> = getifaddrs()
+        .map_err(|_| MacchangerError::ListInterfacesError)?
+        .filter(|address| address.interface_name == interface.name)
+        .filter_map(|address| Some(address.address?.as_sockaddr_in()?.ip()))
+        .collect();
+    targets.extend(read_default_gateways(&interface.name));
+    targets.extend(
+        NetlinkSocket::open()?
+            .get_neighbors()?
+            .into_iter()
+            .filter(|neighbor| neighbor.index == index)
+            .filter_map(|neighbor| match neighbor.destination? {
+                IpAddr::V4(address) => Some(address),
+                IpAddr::V6(_) => None,
+            }),
+    );
+    targets.retain(|target| !target.is_unspecified() && !target.is_multicast());
+    targets.sort();
+    targets.dedup();
+    Ok(targets)
+}
+
+/// The IPv4 default gateways reached through the interface, from `/proc/net/route`.
+fn read_default_gateways(interface_name: &str) -> Vec<Ipv4Addr> {
+    let Ok(routes) = fs::read_to_string("/proc/net/route") else {
+        return vec![];
+    };
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (name, destination, gateway, flags) = (
+                fields.first()?,
+                fields.get(1)?,
+                fields.get(2)?,
+                fields.get(3)?,
+            );
+            let flags = u16::from_str_radix(flags, 16).ok()?;
+            if *name != interface_name || *destination != "00000000" || flags & RTF_GATEWAY == 0 {
+                return None;
+            }
+            // The address is printed as a native endian number holding the network order bytes
+            let gateway = u32::from_str_radix(gateway, 16).ok()?;
+            Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+        })
+        .collect()
+}
+
+/// The state of an interface before a change, used to roll the change back.
+struct LinkSnapshot {
+    mac: HardwareAddr,
+    up: bool,
+}
+
+/// Puts back the MAC address and up/down state of the snapshot, skipping what is unchanged.
+/// Returns whether anything had to be restored.
+fn rollback(interface: &LinuxInterface, snapshot: &LinkSnapshot) -> Result<bool, MacchangerError> {
+    // The link state is restored even if the address can't be, a link left down would cut
+    // off a machine that is managed remotely
+    let mac = rollback_mac(interface, snapshot);
+    let link_state = rollback_link_state(interface, snapshot);
+    match (mac, link_state) {
+        (Ok(mac_restored), Ok(link_state_restored)) => Ok(mac_restored || link_state_restored),
+        (Err(error), Ok(_)) | (Ok(_), Err(error)) => Err(error),
+        (Err(mac), Err(link_state)) => Err(LinuxMacchangerError::RollbackFailed {
+            mac: Box::new(mac),
+            link_state: Box::new(link_state),
+        }
+        .into()),
+    }
+}
+
+fn rollback_mac(
+    interface: &LinuxInterface,
+    snapshot: &LinkSnapshot,
+) -> Result<bool, MacchangerError> {
+    if get_current_mac(interface)? == snapshot.mac {
+        return Ok(false);
+    }
+    apply_mac(interface, &snapshot.mac, &ChangeOptions::default())?;
+    Ok(true)
+}
+
+fn rollback_link_state(
+    interface: &LinuxInterface,
+    snapshot: &LinkSnapshot,
+) -> Result<bool, MacchangerError> {
+    if get_interface_active(interface)? == snapshot.up {
+        return Ok(false);
+    }
+    if change_interface_active(interface, snapshot.up)? != snapshot.up {
+        return Err(match snapshot.up {
+            true => LinuxMacchangerError::InterfaceStillDown(interface.name.clone()),
+            false => LinuxMacchangerError::InterfaceStillUp(interface.name.clone()),
+        }
+        .into());
+    }
+    Ok(true)
+}
+
+fn verify_mac(
+    interface: &LinuxInterface,
+    mac: &HardwareAddr,
+) -> Result<HardwareAddr, MacchangerError> {
+    let actual = get_current_mac(interface)?;
+    if actual != *mac {
+        return Err(MacchangerError::VerificationFailed {
+            requested: mac.clone(),
+            actual,
+        });
+    }
+    Ok(actual)
+}
+
+fn apply_mac(
+    interface: &LinuxInterface,
+    mac: &HardwareAddr,
+    options: &ChangeOptions,
+) -> Result<(), MacchangerError> {
+    let was_up = get_interface_active(interface)?;
+    let live_change = was_up && !options.keep_down && options.link_down != LinkDownPolicy::Always;
+    if !was_up || live_change {
+        match set_hardware_address(interface, mac) {
+            Ok(()) => return Ok(()),
+            // The driver doesn't allow changing the address while the link is up
+            Err(e) if live_change && options.link_down == LinkDownPolicy::Auto && is_busy(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if change_interface_active(interface, false)? {
+        return Err(LinuxMacchangerError::InterfaceStillUp(interface.name.clone()).into());
+    }
+
+    set_hardware_address(interface, mac)?;
+
+    if !options.keep_down && !change_interface_active(interface, true)? {
+        return Err(LinuxMacchangerError::InterfaceStillDown(interface.name.clone()).into());
+    }
+    Ok(())
+}
+
+/// Reads the hardware address the kernel currently uses for the interface, through netlink
+/// or otherwise through `getifaddrs`.
+fn get_current_mac(interface: &LinuxInterface) -> Result<HardwareAddr, MacchangerError> {
+    let index = get_interface_index(interface)?;
+    match NetlinkSocket::open().and_then(|mut netlink| netlink.get_link(index)) {
+        Ok(link) => match link.address {
+            Some(address) => HardwareAddr::new(LinkType(link.link_type), &address),
+            None => Err(LinuxMacchangerError::MacAddressBytes.into()),
+        },
+        Err(e) if netlink_unavailable(&e) => Ok(find_interface(&interface.name)?.adapter.mac),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Checks if a netlink error means rtnetlink can't be used at all, so the legacy ioctl
+/// interface should be tried instead.
+fn netlink_unavailable(error: &LinuxMacchangerError) -> bool {
+    matches!(error, LinuxMacchangerError::NetlinkUnavailable(_))
+}
+
+fn is_busy(error: &LinuxMacchangerError) -> bool {
+    matches!(
+        error,
+        LinuxMacchangerError::SetMacAddress(Errno::EBUSY)
+            | LinuxMacchangerError::Netlink {
+                errno: Errno::EBUSY,
+                ..
+            }
+    )
+}
+
+fn get_interface_index(interface: &LinuxInterface) -> Result<u32, LinuxMacchangerError> {
+    if_nametoindex(interface.name.as_str()).map_err(LinuxMacchangerError::InterfaceIndex)
+}
+
+fn set_hardware_address(
+    interface: &LinuxInterface,
+    mac: &HardwareAddr,
+) -> Result<(), LinuxMacchangerError> {
+    let index = get_interface_index(interface)?;
+    match NetlinkSocket::open().and_then(|mut netlink| netlink.set_address(index, mac.as_bytes())) {
+        Err(e) if netlink_unavailable(&e) => set_hardware_address_ioctl(interface, mac),
+        result => result,
+    }
+}
+
+fn set_hardware_address_ioctl(
+    interface: &LinuxInterface,
+    mac: &HardwareAddr,
+) -> Result<(), LinuxMacchangerError> {
     let socket = get_socket()?;
     let mut mac_bytes_i8: [i8; 14] = [0; 14];
+    // Longer addresses, like those of InfiniBand, don't fit in a struct sockaddr
+    if mac.len() > mac_bytes_i8.len() {
+        return Err(LinuxMacchangerError::SetMacAddress(Errno::EINVAL));
+    }
     for (i, b) in mac.as_bytes().iter().enumerate() {
         mac_bytes_i8[i] = *b as i8;
     }
 
-    let status = change_interface_active(interface, false)?;
-    assert!(!status);
-
     let mut req = IfreqAddress::from(interface);
     // Set the new MAC address bytes
     req.value.sa_data = mac_bytes_i8;
@@ -44,23 +302,54 @@ pub fn change_mac(mac: MacAddr, interface: String) -> Result<MacAddr, Macchanger
         set_mac_address(socket.as_raw_fd(), &mut req)
             .map_err(LinuxMacchangerError::SetMacAddress)?
     };
+    Ok(())
+}
 
-    let status = change_interface_active(interface, true)?;
-    assert!(status);
-    Ok(mac)
+fn get_interface_active(interface: &LinuxInterface) -> Result<bool, LinuxMacchangerError> {
+    let index = get_interface_index(interface)?;
+    match NetlinkSocket::open().and_then(|mut netlink| netlink.get_link(index)) {
+        Ok(link) => Ok(link.flags & IFF_UP as u32 != 0),
+        Err(e) if netlink_unavailable(&e) => {
+            Ok(get_interface_flags_ioctl(interface)? & IFF_UP as u16 != 0)
+        }
+        Err(e) => Err(e),
+    }
 }
 
+/// Sets the interface up or down and returns whether it is up afterwards.
 fn change_interface_active(
     interface: &LinuxInterface,
     active: bool,
 ) -> Result<bool, MacchangerError> {
+    let index = get_interface_index(interface)?;
+    let flags = if active { IFF_UP as u32 } else { 0 };
+    match NetlinkSocket::open()
+        .and_then(|mut netlink| netlink.set_flags(index, flags, IFF_UP as u32))
+    {
+        Err(e) if netlink_unavailable(&e) => change_interface_active_ioctl(interface, active)?,
+        result => result?,
+    }
+    Ok(get_interface_active(interface)?)
+}
+
+fn get_interface_flags_ioctl(interface: &LinuxInterface) -> Result<u16, LinuxMacchangerError> {
     let socket = get_socket()?;
     let mut req = IfreqFlags::from(interface);
     ioctl_read_bad!(get_ifr_flags, SIOCGIFFLAGS, IfreqFlags);
-    ioctl_readwrite_bad!(set_ifr_flags, SIOCSIFFLAGS, IfreqFlags);
     let _ = unsafe {
         get_ifr_flags(socket.as_raw_fd(), &mut req).map_err(LinuxMacchangerError::GetIfrFlags)?
     };
+    Ok(req.value)
+}
+
+fn change_interface_active_ioctl(
+    interface: &LinuxInterface,
+    active: bool,
+) -> Result<(), LinuxMacchangerError> {
+    let socket = get_socket()?;
+    let mut req = IfreqFlags::from(interface);
+    ioctl_readwrite_bad!(set_ifr_flags, SIOCSIFFLAGS, IfreqFlags);
+    req.value = get_interface_flags_ioctl(interface)?;
     match active {
         false => {
             req.value &= !IFF_UP as u16;
@@ -72,8 +361,7 @@ fn change_interface_active(
     let _ = unsafe {
         set_ifr_flags(socket.as_raw_fd(), &mut req).map_err(LinuxMacchangerError::SetIfrFlags)?
     };
-    let status = req.value & IFF_UP as u16 == 1;
-    Ok(status)
+    Ok(())
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -88,14 +376,10 @@ impl From<&LinuxInterface> for IfreqAddress {
         let mut req = IfreqAddress {
             name: [0; IF_NAMESIZE],
             value: sockaddr {
-                sa_family: ARPHRD_ETHER,
+                sa_family: interface.adapter.mac.link_type().0,
                 sa_data: [0; 14],
             },
         };
-        let mut mac_bytes_i8: [i8; 14] = [0; 14];
-        for (i, b) in interface.adapter.mac.as_bytes().iter().enumerate() {
-            mac_bytes_i8[i] = *b as i8;
-        }
         req.name
             .as_mut()
             .write_all(interface.name.as_bytes())
@@ -131,13 +415,30 @@ impl From<&LinuxInterface> for IfreqFlags {
 pub struct LinuxInterface {
     pub name: String,
     pub adapter: LinuxAdapter,
+    pub index: u32,
+    pub flags: u32,
+    pub operstate: OperState,
+    pub carrier: Option<bool>,
+    pub mtu: u32,
+    pub origin: AddressOrigin,
+    pub permanent_mac: Option<HardwareAddr>,
+    pub warnings: Vec<String>,
 }
 
 impl From<LinuxInterface> for Interface {
     fn from(val: LinuxInterface) -> Self {
         Interface {
             name: val.name,
-            mac: val.adapter.mac,
+            mac: val.adapter.mac.clone(),
+            index: val.index,
+            flags: val.flags,
+            operstate: val.operstate,
+            carrier: val.carrier,
+            mtu: val.mtu,
+            origin: val.origin,
+            permanent_mac: val.permanent_mac,
+            warnings: val.warnings,
+            adapter: val.adapter.into(),
         }
     }
 }
@@ -164,6 +465,38 @@ pub enum LinuxMacchangerError {
     SetIfrFlags(Errno),
     #[error("Something went wrong with getting the permanent MacAddress: {0}")]
     GetPermanentMac(Errno),
+    #[error("Something went wrong with reading from sysfs: {0}")]
+    Sysfs(String),
+    #[error("Something went wrong with the MAC address journal: {0}")]
+    Journal(String),
+    #[error("Something went wrong with probing for the MAC address: {0}")]
+    Probe(Errno),
+    #[error("Only Ethernet addresses can be probed with ARP, not {0} addresses")]
+    ProbeUnsupported(LinkType),
+    #[error("The interface {0} is down, bring it up to probe for the MAC address")]
+    ProbeLinkDown(String),
+    #[error("Restoring the MAC address failed: {mac}, restoring the link state failed too: {link_state}")]
+    RollbackFailed {
+        mac: Box<MacchangerError>,
+        link_state: Box<MacchangerError>,
+    },
+    #[error("The interface {0} is still up after trying to take it down")]
+    InterfaceStillUp(String),
+    #[error("The interface {0} is still down after trying to bring it back up")]
+    InterfaceStillDown(String),
+    #[error("Something went wrong with looking up the interface index: {0}")]
+    InterfaceIndex(Errno),
+    #[error("Netlink is not available: {0}")]
+    NetlinkUnavailable(Errno),
+    #[error("Something went wrong with the netlink socket: {0}")]
+    NetlinkSocket(Errno),
+    #[error("Something went wrong with parsing a netlink message")]
+    NetlinkMessage,
+    #[error("The kernel rejected the netlink request: {errno}{}", .message.as_ref().map(|m| format!(" ({m})")).unwrap_or_default())]
+    Netlink {
+        errno: Errno,
+        message: Option<String>,
+    },
 }
 
 impl From<LinuxMacchangerError> for MacchangerError {
@@ -187,56 +520,52 @@ impl TryFrom<InterfaceAddress> for OptionalLinuxInterface {
             None => return Err(LinuxMacchangerError::SocketaddrStorage),
         };
 
-        if socket_address.as_link_addr().is_none() {
+        let Some(link_address) = socket_address.as_link_addr() else {
             return Ok(OptionalLinuxInterface(None));
+        };
+        let name = interface.interface_name;
+        let link_type = LinkType(link_address.hatype());
+        let address_bytes = &link_address.as_ref().sll_addr;
+        let mut warnings = vec![];
+        let mac = match link_address.halen() {
+            0 => return Ok(OptionalLinuxInterface(None)),
+            length if length <= address_bytes.len() => {
+                HardwareAddr::new(link_type, &address_bytes[..length])
+            }
+            // getifaddrs truncates longer addresses, like those of InfiniBand, sysfs has them whole
+            _ => read_sysfs_attribute(&name, "address")
+                .map_err(MacchangerError::from)
+                .and_then(|address| HardwareAddr::parse(link_type, &address))
+                .or_else(|e| {
+                    warnings.push(format!(
+                        "only the first {} bytes of the address could be read: {}",
+                        address_bytes.len(),
+                        e
+                    ));
+                    HardwareAddr::new(link_type, address_bytes)
+                }),
         }
-
-        let link_address = socket_address.as_link_addr().unwrap();
-        if link_address.addr().unwrap().iter().all(|b| *b == 0) {
+        .map_err(|_| LinuxMacchangerError::MacAddressBytes)?;
+        if mac.is_zero() {
             return Ok(OptionalLinuxInterface(None));
         }
-        let mac = match link_address.addr() {
-            Some(address_bytes) => MacAddr::from(address_bytes),
-            None => return Err(LinuxMacchangerError::MacAddressBytes),
-        };
 
-        let vendor_path = format!("/sys/class/net/{}/device/vendor", &interface.interface_name);
-        let device_path = format!("/sys/class/net/{}/device/device", &interface.interface_name);
-
-        let adapter_name = if !Path::new(&vendor_path).exists() || !Path::new(&device_path).exists()
-        {
-            "Virtual Adapter (non-existent)"
-        } else {
-            let vendor_string = fs::read_to_string(vendor_path)
-                .map_err(|e| LinuxMacchangerError::AdapterNameLookup(e.to_string()))?;
-
-            let vendor_string = vendor_string.trim_end();
-            let vendor_string = vendor_string.strip_prefix("0x").unwrap();
-
-            let vendor_id = u16::from_str_radix(vendor_string, 16)
-                .map_err(|e| LinuxMacchangerError::AdapterNameLookup(e.to_string()))?;
-
-            let device_string = fs::read_to_string(device_path)
-                .map_err(|e| LinuxMacchangerError::AdapterNameLookup(e.to_string()))?;
-            let device_string = device_string.trim_end();
-            let device_string = device_string.strip_prefix("0x").unwrap();
-            let device_id = u16::from_str_radix(device_string, 16)
-                .map_err(|e| LinuxMacchangerError::AdapterNameLookup(e.to_string()))?;
-
-            let device = Device::from_vid_pid(vendor_id, device_id).ok_or(
-                LinuxMacchangerError::AdapterNameLookup(
-                    "Could not find network adapter by vendor id and device id".to_owned(),
-                ),
-            )?;
-            device.name()
-        };
-        let interface = LinuxInterface {
-            name: interface.clone().interface_name,
-            adapter: LinuxAdapter {
-                name: adapter_name.to_owned(),
-                mac,
-            },
+        let mut interface = LinuxInterface {
+            adapter: read_adapter(&name, mac),
+            index: read_sysfs_number(&name, "ifindex").unwrap_or_default(),
+            flags: read_sysfs_number(&name, "flags").unwrap_or_default(),
+            operstate: read_operstate(&name),
+            // Reading the carrier fails while the interface is down
+            carrier: read_sysfs_attribute(&name, "carrier")
+                .ok()
+                .map(|carrier| carrier == "1"),
+            mtu: read_sysfs_number(&name, "mtu").unwrap_or_default(),
+            origin: read_address_origin(&name).unwrap_or(AddressOrigin::Unknown),
+            permanent_mac: None,
+            warnings,
+            name,
         };
+        interface.permanent_mac = find_permanent_mac(&interface).ok().map(|h| h.mac);
 
         Ok(OptionalLinuxInterface(Some(interface)))
     }
@@ -245,58 +574,218 @@ impl TryFrom<InterfaceAddress> for OptionalLinuxInterface {
 #[derive(Debug, Clone)]
 pub struct LinuxAdapter {
     pub name: String,
-    pub mac: MacAddr,
+    pub mac: HardwareAddr,
+    pub kind: AdapterKind,
+    pub driver: Option<String>,
+    pub bus_path: Option<String>,
+    pub ids: Option<DeviceIds>,
+    pub warnings: Vec<String>,
 }
 
 impl From<LinuxAdapter> for Adapter {
     fn from(val: LinuxAdapter) -> Self {
-        Adapter { name: val.name }
+        Adapter {
+            name: val.name,
+            kind: val.kind,
+            driver: val.driver,
+            bus_path: val.bus_path,
+            ids: val.ids,
+            warnings: val.warnings,
+        }
     }
 }
-pub fn list_interfaces() -> Result<Vec<LinuxInterface>, MacchangerError> {
-    let mut addrs = getifaddrs().map_err(|_| MacchangerError::ListInterfacesError)?;
-    let mut interfaces: Vec<LinuxInterface> = vec![];
-    let r = addrs.try_for_each(|i| {
-        let interface_result = OptionalLinuxInterface::try_from(i);
-        if interface_result.is_err() {
-            ControlFlow::Break(interface_result)
-        } else {
-            let optional_interface = interface_result.unwrap();
-            if let Some(interface) = optional_interface.0 {
-                interfaces.push(interface);
-            }
 
-            ControlFlow::Continue(())
+/// Gathers the details of the device behind the interface. Its name is looked up in the PCI
+/// or USB ID database, or derived from its driver for other buses such as SDIO or platform
+/// devices. Failing to identify the device only results in warnings, so it never hides the
+/// interface itself.
+fn read_adapter(interface_name: &str, mac: HardwareAddr) -> LinuxAdapter {
+    let mut adapter = LinuxAdapter {
+        name: "Virtual Adapter (non-existent)".to_owned(),
+        mac,
+        kind: get_kind_from_sysfs(interface_name),
+        driver: None,
+        bus_path: None,
+        ids: None,
+        warnings: vec![],
+    };
+    let device_path = Path::new("/sys/class/net")
+        .join(interface_name)
+        .join("device");
+    let Ok(real_device_path) = fs::canonicalize(&device_path) else {
+        return adapter;
+    };
+    adapter.driver = get_link_name(&device_path.join("driver"));
+    adapter.bus_path = real_device_path
+        .strip_prefix("/sys/devices")
+        .ok()
+        .map(|path| path.display().to_string());
+
+    let subsystem = get_link_name(&device_path.join("subsystem"));
+    let ids_device = match subsystem.as_deref() {
+        Some("pci") => Some((real_device_path.clone(), DeviceBus::Pci)),
+        // The interface belongs to a USB interface, the IDs are on its parent USB device
+        Some("usb") => real_device_path
+            .parent()
+            .map(|path| (path.to_owned(), DeviceBus::Usb)),
+        _ => None,
+    };
+
+    let mut database_name = None;
+    if let Some((ids_path, bus)) = ids_device {
+        match read_device_ids(&ids_path, bus) {
+            Ok(ids) => {
+                adapter.ids = Some(ids);
+                database_name = lookup_device_name(ids, &ids_path);
+                if database_name.is_none() {
+                    let error = LinuxMacchangerError::AdapterNameLookup(format!(
+                        "{} device {:04x}:{:04x} is not in the {} ID database",
+                        bus, ids.vendor_id, ids.device_id, bus
+                    ));
+                    adapter.warnings.push(error.to_string());
+                }
+            }
+            Err(e) => adapter.warnings.push(e.to_string()),
         }
+    }
+
+    adapter.name = database_name.unwrap_or_else(|| match (&adapter.driver, subsystem) {
+        (Some(driver), Some(subsystem)) => format!("{} ({} device)", driver, subsystem),
+        (Some(driver), None) => driver.clone(),
+        (None, Some(subsystem)) => format!("Unknown {} device", subsystem),
+        (None, None) => "Unknown device".to_owned(),
     });
-    if let ControlFlow::Break(Err(e)) = r {
-        Err(MacchangerError::LinuxError(e))
-    } else {
-        Ok(interfaces)
+    adapter
+}
+
+/// The name of the file a sysfs symlink (such as `subsystem` or `driver`) points to.
+fn get_link_name(path: &Path) -> Option<String> {
+    fs::read_link(path)
+        .ok()?
+        .file_name()?
+        .to_str()
+        .map(str::to_owned)
+}
+
+fn read_device_ids(device_path: &Path, bus: DeviceBus) -> Result<DeviceIds, LinuxMacchangerError> {
+    let (vendor_attribute, device_attribute) = match bus {
+        DeviceBus::Pci => ("vendor", "device"),
+        DeviceBus::Usb => ("idVendor", "idProduct"),
+    };
+    let read_id = |attribute: &str| {
+        let id_string = fs::read_to_string(device_path.join(attribute))
+            .map_err(|e| LinuxMacchangerError::AdapterNameLookup(e.to_string()))?;
+        u16::from_str_radix(id_string.trim_end().trim_start_matches("0x"), 16)
+            .map_err(|e| LinuxMacchangerError::AdapterNameLookup(e.to_string()))
+    };
+    Ok(DeviceIds {
+        bus,
+        vendor_id: read_id(vendor_attribute)?,
+        device_id: read_id(device_attribute)?,
+    })
+}
+
+/// Looks up the device in the PCI or USB ID database. USB devices not in the database
+/// usually still report their product name themselves.
+fn lookup_device_name(ids: DeviceIds, device_path: &Path) -> Option<String> {
+    match ids.bus {
+        DeviceBus::Pci => Device::from_vid_pid(ids.vendor_id, ids.device_id)
+            .map(|device| device.name().to_owned()),
+        DeviceBus::Usb => usb_ids::Device::from_vid_pid(ids.vendor_id, ids.device_id)
+            .map(|device| device.name().to_owned())
+            .or_else(|| {
+                fs::read_to_string(device_path.join("product"))
+                    .ok()
+                    .map(|product| product.trim_end().to_owned())
+            }),
     }
 }
 
-pub fn list_adapters() -> Result<Vec<LinuxAdapter>, MacchangerError> {
-    let mut addrs = getifaddrs().map_err(|_| MacchangerError::ListInterfacesError)?;
-    let mut adapters: Vec<LinuxAdapter> = vec![];
-    let r = addrs.try_for_each(|i| {
-        let interface_result = OptionalLinuxInterface::try_from(i);
-        if interface_result.is_err() {
-            ControlFlow::Break(interface_result)
-        } else {
-            let optional_interface = interface_result.unwrap();
-            if let Some(interface) = optional_interface.0 {
-                adapters.push(interface.adapter);
-            }
+fn read_sysfs_number(interface_name: &str, attribute: &str) -> Option<u32> {
+    let value = read_sysfs_attribute(interface_name, attribute).ok()?;
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
 
-            ControlFlow::Continue(())
-        }
-    });
-    if let ControlFlow::Break(Err(e)) = r {
-        Err(MacchangerError::LinuxError(e))
+fn read_operstate(interface_name: &str) -> OperState {
+    match read_sysfs_attribute(interface_name, "operstate").as_deref() {
+        Ok("up") => OperState::Up,
+        Ok("down") => OperState::Down,
+        Ok("dormant") => OperState::Dormant,
+        Ok("lowerlayerdown") => OperState::LowerLayerDown,
+        Ok("notpresent") => OperState::NotPresent,
+        Ok("testing") => OperState::Testing,
+        _ => OperState::Unknown,
+    }
+}
+
+fn get_kind_from_sysfs(interface_name: &str) -> AdapterKind {
+    let interface_path = Path::new("/sys/class/net").join(interface_name);
+    if interface_path.join("wireless").exists() || interface_path.join("phy80211").exists() {
+        AdapterKind::Wireless
+    } else if interface_path.join("device").exists() {
+        AdapterKind::Wired
     } else {
-        Ok(adapters)
+        AdapterKind::Virtual
+    }
+}
+
+pub fn get_interface(interface: String) -> Result<LinuxInterface, MacchangerError> {
+    find_interface(&interface)
+}
+
+pub fn get_adapter_kind(interface: String) -> Result<AdapterKind, MacchangerError> {
+    Ok(find_interface(&interface)?.adapter.kind)
+}
+
+/// Finds the interface with the given name, or explains why it can't be used: either it
+/// doesn't exist (with similarly named interfaces as suggestions) or it has no MAC address.
+fn find_interface(name: &str) -> Result<LinuxInterface, MacchangerError> {
+    let interfaces = list_interfaces()?;
+    if let Some(interface) = interfaces.iter().find(|i| i.name == name) {
+        return Ok(interface.clone());
     }
+
+    if !name.is_empty() && !name.contains('/') && Path::new("/sys/class/net").join(name).exists() {
+        return Err(MacchangerError::NoLinkLayerAddress(name.to_owned()));
+    }
+    Err(MacchangerError::InterfaceNotFound {
+        name: name.to_owned(),
+        candidates: similar_names(name, interfaces.iter().map(|i| i.name.as_str())),
+    })
+}
+
+pub fn list_interfaces() -> Result<Vec<LinuxInterface>, MacchangerError> {
+    let addrs = getifaddrs().map_err(|_| MacchangerError::ListInterfacesError)?;
+    // Details that can't be read end up as warnings on the interface, so it is never hidden
+    let interfaces = addrs
+        .filter_map(|i| OptionalLinuxInterface::try_from(i).map(|i| i.0).transpose())
+        .collect::<Result<_, _>>()?;
+    Ok(interfaces)
+}
+
+/// The link-layer addresses of the neighbors in the kernel's ARP and NDP tables.
+pub fn list_neighbor_addresses() -> Result<Vec<HardwareAddr>, MacchangerError> {
+    let neighbors = NetlinkSocket::open()?.get_neighbors()?;
+    Ok(neighbors
+        .into_iter()
+        // Failed entries may still hold the address of a neighbor that has since gone away
+        .filter(|neighbor| neighbor.state & NUD_FAILED == 0)
+        .filter_map(|neighbor| {
+            let address = neighbor.address?;
+            HardwareAddr::new(LinkType::from_address_len(address.len()), &address).ok()
+        })
+        .collect())
+}
+
+pub fn list_adapters() -> Result<Vec<LinuxAdapter>, MacchangerError> {
+    let adapters = list_interfaces()?
+        .into_iter()
+        .map(|interface| interface.adapter)
+        .collect();
+    Ok(adapters)
 }
 
 const MAX_ADDR_LEN: u32 = 32;
@@ -316,13 +805,103 @@ struct IfreqEthtool {
     value: *mut EthtoolRequest,
 }
 
-pub fn get_hardware_mac(interface: String) -> Result<MacAddr, MacchangerError> {
-    let interfaces = list_interfaces()?;
-    let temp_interface = interfaces
-        .into_iter()
-        .filter(|i| i.name == interface)
-        .collect::<Vec<LinuxInterface>>();
-    let interface = temp_interface.first().unwrap();
+/// Retrieves the permanent MAC address of the interface from the first source that knows it.
+/// Virtual devices and some drivers don't have one, in which case the address from before
+/// macchanger first changed the interface is used instead.
+pub fn get_hardware_mac(interface: String) -> Result<HardwareMac, MacchangerError> {
+    find_permanent_mac(&find_interface(&interface)?)
+}
+
+fn find_permanent_mac(interface: &LinuxInterface) -> Result<HardwareMac, MacchangerError> {
+    let sources: [(MacSource, PermanentMacSource); 4] = [
+        (MacSource::Ethtool, get_permanent_mac_ethtool),
+        (MacSource::Netlink, get_permanent_mac_netlink),
+        (MacSource::Sysfs, get_permanent_mac_sysfs),
+        (MacSource::Journal, get_permanent_mac_journal),
+    ];
+
+    let mut attempts = vec![];
+    for (source, lookup) in sources {
+        match lookup(interface) {
+            Ok(Some(mac)) if !mac.is_zero() => return Ok(HardwareMac { mac, source }),
+            Ok(_) => attempts.push(format!(
+                "{}: {}",
+                source,
+                match source {
+                    MacSource::Ethtool => "the driver reports no permanent address",
+                    MacSource::Netlink => "the kernel reports no permanent address",
+                    MacSource::Sysfs => "the current address is not the permanent one",
+                    _ => "the interface was never changed by macchanger",
+                }
+            )),
+            Err(e) => attempts.push(format!("{}: {}", source, e)),
+        }
+    }
+    Err(MacchangerError::NoPermanentMac {
+        interface: interface.name.clone(),
+        attempts,
+    })
+}
+
+type PermanentMacSource = fn(&LinuxInterface) -> Result<Option<HardwareAddr>, MacchangerError>;
+
+fn get_permanent_mac_netlink(
+    interface: &LinuxInterface,
+) -> Result<Option<HardwareAddr>, MacchangerError> {
+    let index = get_interface_index(interface)?;
+    let link = NetlinkSocket::open()?.get_link(index)?;
+    link.permanent_address
+        .map(|address| HardwareAddr::new(LinkType(link.link_type), &address))
+        .transpose()
+}
+
+/// Reads the current address from sysfs, but only if the kernel says it is the permanent one.
+fn get_permanent_mac_sysfs(
+    interface: &LinuxInterface,
+) -> Result<Option<HardwareAddr>, MacchangerError> {
+    if read_address_origin(&interface.name)? != AddressOrigin::Permanent {
+        return Ok(None);
+    }
+    let address = read_sysfs_attribute(&interface.name, "address")?;
+    HardwareAddr::parse(interface.adapter.mac.link_type(), &address).map(Some)
+}
+
+/// Reads how the kernel assigned the current address of the interface (`addr_assign_type`).
+fn read_address_origin(interface_name: &str) -> Result<AddressOrigin, LinuxMacchangerError> {
+    // NET_ADDR_* from include/uapi/linux/netdevice.h
+    Ok(
+        match read_sysfs_attribute(interface_name, "addr_assign_type")?.as_str() {
+            "0" => AddressOrigin::Permanent,
+            "1" => AddressOrigin::Random,
+            "2" => AddressOrigin::Stolen,
+            "3" => AddressOrigin::Set,
+            _ => AddressOrigin::Unknown,
+        },
+    )
+}
+
+fn read_sysfs_attribute(
+    interface_name: &str,
+    attribute: &str,
+) -> Result<String, LinuxMacchangerError> {
+    fs::read_to_string(
+        Path::new("/sys/class/net")
+            .join(interface_name)
+            .join(attribute),
+    )
+    .map(|value| value.trim().to_owned())
+    .map_err(|e| LinuxMacchangerError::Sysfs(e.to_string()))
+}
+
+fn get_permanent_mac_journal(
+    interface: &LinuxInterface,
+) -> Result<Option<HardwareAddr>, MacchangerError> {
+    Ok(Journal::open()?.original_mac(&interface.name).cloned())
+}
+
+fn get_permanent_mac_ethtool(
+    interface: &LinuxInterface,
+) -> Result<Option<HardwareAddr>, MacchangerError> {
     let socket = get_socket()?;
     let mut epa = EthtoolRequest {
         cmd: 0x00000020,
@@ -345,16 +924,10 @@ pub fn get_hardware_mac(interface: String) -> Result<MacAddr, MacchangerError> {
             .map_err(LinuxMacchangerError::GetPermanentMac)?
     };
 
-    match epa.size {
-        6 => {
-            let mac_data: [u8; 6] = epa.data[0..6].try_into().unwrap();
-            Ok(MacAddr::from(mac_data))
-        }
-        8 => {
-            let mac_data: [u8; 8] = epa.data[0..6].try_into().unwrap();
-            Ok(MacAddr::from(mac_data))
-        }
-        _ => Err(MacchangerError::LinuxError(
+    match epa.data.get(..epa.size as usize) {
+        Some([]) => Ok(None),
+        Some(address) => HardwareAddr::new(interface.adapter.mac.link_type(), address).map(Some),
+        None => Err(MacchangerError::LinuxError(
             LinuxMacchangerError::MacAddressBytes,
         )),
     }
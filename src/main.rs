@@ -1,10 +1,19 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{bail, Result};
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
 use macaddr::MacAddr;
 use macchanger_lib::{
-    do_change_mac, generate_random_mac, retrieve_adapters, retrieve_hardware_mac,
-    retrieve_interfaces,
+    discover_hardware_mac, do_change_hardware_address, do_change_mac_with_options, generate_mac,
+    generate_random_vendor_mac, generate_same_kind_mac, generate_unused_mac,
+    generate_vendor_preserving_mac, import_oui_database, oui_registry, retrieve_adapters,
+    retrieve_interface, retrieve_interfaces, AddressOrigin, ChangeOptions, GenerateOptions,
+    HardwareAddr, Interface, LinkDownPolicy, MacGenerator, MacPattern, MacSource, MacchangerError,
+    OsGenerator, SeededGenerator, SlapQuadrant,
 };
+use output::{OutputFormat, Table};
+use serde::Serialize;
+use std::path::PathBuf;
+
+mod output;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -22,69 +31,582 @@ enum Commands {
         interface: String,
         /// Optional MAC address to change to
         mac: Option<MacAddr>,
+        /// Keep the vendor bytes (OUI) of the interface's MAC address and only randomize the rest
+        #[arg(short = 'e', long, conflicts_with = "mac")]
+        keep_vendor: bool,
+        /// Use a random vendor prefix of the same kind of adapter (e.g. wireless or wired). The
+        /// kind is guessed from the vendor name, vendors making both count as wired
+        #[arg(short = 'a', long, conflicts_with_all = ["mac", "keep_vendor"])]
+        same_kind: bool,
+        /// Use a random vendor prefix of any kind of adapter
+        #[arg(short = 'A', long, conflicts_with_all = ["mac", "keep_vendor", "same_kind"])]
+        any_kind: bool,
+        /// Generate a random address in the given IEEE 802c SLAP quadrant
+        #[arg(long, value_name = "QUADRANT", conflicts_with_all = ["mac", "keep_vendor", "same_kind", "any_kind"])]
+        slap: Option<Quadrant>,
+        /// Generate a random address inside a prefix (02:aa:bb:00:00:00/24) or template (00:1b:21:??:??:??)
+        #[arg(long, value_name = "PATTERN", conflicts_with_all = ["mac", "keep_vendor", "same_kind", "any_kind", "slap"])]
+        prefix: Option<MacPattern>,
+        /// Source of the random bytes for generated addresses, the OS CSPRNG by default
+        #[arg(long, value_enum, conflicts_with = "mac")]
+        rng: Option<RngKind>,
+        /// Seed for reproducible addresses, implies `--rng seeded`
+        #[arg(long, conflicts_with = "mac", required_if_eq("rng", "seeded"))]
+        seed: Option<u64>,
+        #[command(flatten)]
+        change_args: ChangeArgs,
+        #[command(flatten)]
+        output_args: OutputArgs,
     },
     /// Lists all interfaces on the current system. (On Linux, it only shows interfaces with an associated MAC address)
-    ListInterfaces,
+    ListInterfaces {
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
     /// Lists all network adatpers on the current system. (On Linux, it only shows phsyical network adapters)
-    ListAdapters,
+    ListAdapters {
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
     /// Lists all interfaces together with their MAC addresses
-    ListMacs,
-    /// Restores the MAC address of the interface to the original (hardware-defined) one.
+    ListMacs {
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
+    /// Shows all details of an interface and the adapter behind it
+    Show {
+        /// Interface to show the details of
+        interface: String,
+    },
+    /// Looks up the vendor a MAC address was assigned to in the IEEE OUI registry
+    Lookup {
+        /// MAC address to look up
+        mac: MacAddr,
+    },
+    /// Searches or lists the vendor prefixes (OUIs) in the IEEE OUI registry
+    #[command(arg_required_else_help = true, args_conflicts_with_subcommands = true)]
+    Vendors {
+        #[command(subcommand)]
+        command: Option<VendorsCommands>,
+        /// Lists all known vendor prefixes
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Imports a locally downloaded OUI database (IEEE oui.txt/oui.csv or Wireshark manuf) to use for vendor lookups
+    ImportOui {
+        /// Database files to import
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Restores the MAC address of the interface to the original (hardware-defined) one, or to the one it had before macchanger first changed it
     Restore {
         /// Interface to restore the orignal MAC address of
         interface: String,
+        #[command(flatten)]
+        change_args: ChangeArgs,
+        #[command(flatten)]
+        output_args: OutputArgs,
     },
 }
 
+/// The IEEE 802c SLAP quadrants, see [`SlapQuadrant`].
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Quadrant {
+    /// Administratively Assigned Identifier (x2)
+    Aai,
+    /// Extended Local Identifier (xA)
+    Eli,
+    /// Standard Assigned Identifier (xE)
+    Sai,
+    /// Reserved for future use (x6)
+    Reserved,
+}
+
+impl From<Quadrant> for SlapQuadrant {
+    fn from(quadrant: Quadrant) -> Self {
+        match quadrant {
+            Quadrant::Aai => SlapQuadrant::Aai,
+            Quadrant::Eli => SlapQuadrant::Eli,
+            Quadrant::Sai => SlapQuadrant::Sai,
+            Quadrant::Reserved => SlapQuadrant::Reserved,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum RngKind {
+    /// The operating system's cryptographically secure random number generator
+    Os,
+    /// ChaCha20 seeded with `--seed`, which generates the same addresses for the same seed
+    Seeded,
+}
+
+#[derive(ClapArgs, Debug)]
+struct ChangeArgs {
+    /// Leave the interface down after the change instead of restoring its previous state
+    #[arg(long)]
+    keep_down: bool,
+    /// Never take the interface down, fail if the driver doesn't allow changing the MAC address while it is up
+    #[arg(long, conflicts_with_all = ["force_down", "keep_down"])]
+    no_down: bool,
+    /// Always take the interface down while changing the MAC address
+    #[arg(long)]
+    force_down: bool,
+    /// Send ARP probes from the new MAC address first and refuse it (or generate another one) when another host answers from it. Only neighbors and gateways the interface knows about are asked, silent hosts may go unnoticed
+    #[arg(long)]
+    probe: bool,
+    /// Use the MAC address even if it is a multicast, broadcast, all-zero or reserved address
+    #[arg(long)]
+    force: bool,
+}
+
+impl From<&ChangeArgs> for ChangeOptions {
+    fn from(args: &ChangeArgs) -> Self {
+        let link_down = if args.no_down {
+            LinkDownPolicy::Never
+        } else if args.force_down {
+            LinkDownPolicy::Always
+        } else {
+            LinkDownPolicy::Auto
+        };
+        ChangeOptions {
+            keep_down: args.keep_down,
+            link_down,
+            probe: args.probe,
+            force: args.force,
+        }
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+struct OutputArgs {
+    /// Print the result in a structured format instead of as text
+    #[arg(short, long, value_enum)]
+    output: Option<OutputFormat>,
+}
+
+/// The MAC address of an interface as printed by `list-macs`.
+#[derive(Serialize)]
+struct MacEntry {
+    interface: String,
+    mac: String,
+    vendor: Option<String>,
+    origin: AddressOrigin,
+    spoofed: bool,
+}
+
+/// The result of `change` and `restore`.
+#[derive(Serialize)]
+struct ChangeReport {
+    interface: String,
+    mac: String,
+    /// Where the original MAC address was found, for `restore`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<MacSource>,
+    /// How many generated addresses were discarded because they were in use already, for
+    /// `change` without an explicit address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries: Option<u32>,
+    /// How many generated addresses were discarded because probing found them in use on the
+    /// link, for `change --probe` without an explicit address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    probe_retries: Option<u32>,
+}
+
+#[derive(Subcommand, Debug)]
+enum VendorsCommands {
+    /// Searches the vendor prefixes by vendor name or prefix
+    Search {
+        /// Text to search for (case insensitive)
+        pattern: String,
+        /// Interpret the pattern as a regular expression matched against the vendor name
+        #[arg(short, long)]
+        regex: bool,
+    },
+}
+
+/// How often `change` generates a new address when probing finds the previous one in use.
+const MAX_PROBE_RETRIES: u32 = 8;
+
 fn main() -> Result<()> {
     let args = Args::parse();
 
     match &args.command {
-        Commands::ListInterfaces => {
+        Commands::ListInterfaces { output_args } => {
             let interfaces = retrieve_interfaces()?;
-            println!("Found {} interfaces", interfaces.len());
-            for interface in interfaces {
-                println!("{}", interface.name);
+            if let Some(format) = output_args.output {
+                let mut table =
+                    Table::new(vec!["name", "mac", "index", "operstate", "mtu", "adapter"]);
+                for interface in &interfaces {
+                    table.row(vec![
+                        interface.name.clone(),
+                        interface.mac.to_string(),
+                        interface.index.to_string(),
+                        interface.operstate.to_string(),
+                        interface.mtu.to_string(),
+                        interface.adapter.name.clone(),
+                    ]);
+                }
+                output::print(format, &interfaces, table)?;
+            } else {
+                println!("Found {} interfaces", interfaces.len());
+                for interface in &interfaces {
+                    println!("{}", interface.name);
+                }
+            }
+            for interface in &interfaces {
+                print_interface_warnings(interface);
             }
         }
-        Commands::ListMacs => {
+        Commands::ListMacs { output_args } => {
             let interfaces = retrieve_interfaces()?;
-            println!("Found {} MAC addresses", interfaces.len());
-            for interface in interfaces {
-                println!(
-                    "Interface: {} - MAC address: {}",
-                    interface.name, interface.mac
-                );
+            let registry = oui_registry()?;
+            let entries: Vec<MacEntry> = interfaces
+                .iter()
+                .map(|interface| MacEntry {
+                    interface: interface.name.clone(),
+                    mac: interface.mac.to_string(),
+                    vendor: interface
+                        .mac
+                        .to_mac()
+                        .and_then(|mac| registry.lookup(mac))
+                        .map(|v| v.name.clone()),
+                    origin: interface.origin,
+                    spoofed: interface.is_spoofed(),
+                })
+                .collect();
+            if let Some(format) = output_args.output {
+                let mut table = Table::new(vec!["interface", "mac", "vendor", "origin", "spoofed"]);
+                for entry in &entries {
+                    table.row(vec![
+                        entry.interface.clone(),
+                        entry.mac.clone(),
+                        entry.vendor.clone().unwrap_or_default(),
+                        entry.origin.to_string(),
+                        entry.spoofed.to_string(),
+                    ]);
+                }
+                output::print(format, &entries, table)?;
+            } else {
+                println!("Found {} MAC addresses", entries.len());
+                for entry in &entries {
+                    println!(
+                        "Interface: {} - MAC address: {} - Vendor: {} - Origin: {}{}",
+                        entry.interface,
+                        entry.mac,
+                        entry.vendor.as_deref().unwrap_or("Unknown"),
+                        entry.origin,
+                        if entry.spoofed { " (spoofed)" } else { "" }
+                    );
+                }
+            }
+            for interface in &interfaces {
+                print_interface_warnings(interface);
             }
         }
-        Commands::ListAdapters => {
+        Commands::Show { interface } => {
+            let interface = retrieve_interface(interface.clone())?;
+            let unknown = || "Unknown".to_owned();
+            println!("Interface: {}", interface.name);
+            println!("  Index: {}", interface.index);
+            println!("  MAC address: {}", interface.mac);
+            println!("  Link type: {}", interface.mac.link_type());
+            println!(
+                "  Permanent MAC address: {}",
+                interface
+                    .permanent_mac
+                    .as_ref()
+                    .map(|mac| mac.to_string())
+                    .unwrap_or_else(unknown)
+            );
+            println!(
+                "  Address origin: {}{}",
+                interface.origin,
+                if interface.is_spoofed() {
+                    " (spoofed)"
+                } else {
+                    ""
+                }
+            );
+            let registry = oui_registry()?;
+            println!(
+                "  Vendor: {}",
+                interface
+                    .mac
+                    .to_mac()
+                    .and_then(|mac| registry.lookup(mac))
+                    .map(|v| v.name.clone())
+                    .unwrap_or_else(unknown)
+            );
+            println!(
+                "  Flags: {:#x} <{}>",
+                interface.flags,
+                flag_names(interface.flags).join(",")
+            );
+            println!("  Operational state: {}", interface.operstate);
+            println!(
+                "  Carrier: {}",
+                match interface.carrier {
+                    Some(true) => "yes",
+                    Some(false) => "no",
+                    None => "unknown",
+                }
+            );
+            println!("  MTU: {}", interface.mtu);
+            let adapter = &interface.adapter;
+            println!("Adapter: {}", adapter.name);
+            println!("  Kind: {}", adapter.kind);
+            println!(
+                "  Driver: {}",
+                adapter.driver.clone().unwrap_or_else(unknown)
+            );
+            println!(
+                "  Bus path: {}",
+                adapter.bus_path.clone().unwrap_or_else(unknown)
+            );
+            println!(
+                "  Device IDs: {}",
+                adapter
+                    .ids
+                    .map(|ids| ids.to_string())
+                    .unwrap_or_else(unknown)
+            );
+            print_interface_warnings(&interface);
+        }
+        Commands::ListAdapters { output_args } => {
             let adapters = retrieve_adapters()?;
-            println!("Found {} adapters", adapters.len());
-            for adapter in adapters {
-                println!("{}", adapter.name);
+            if let Some(format) = output_args.output {
+                let mut table = Table::new(vec!["name", "kind", "driver", "bus_path", "ids"]);
+                for adapter in &adapters {
+                    table.row(vec![
+                        adapter.name.clone(),
+                        adapter.kind.to_string(),
+                        adapter.driver.clone().unwrap_or_default(),
+                        adapter.bus_path.clone().unwrap_or_default(),
+                        adapter.ids.map(|ids| ids.to_string()).unwrap_or_default(),
+                    ]);
+                }
+                output::print(format, &adapters, table)?;
+            } else {
+                println!("Found {} adapters", adapters.len());
+                for adapter in &adapters {
+                    println!("{}", adapter.name);
+                }
+            }
+            for adapter in &adapters {
+                print_warnings(&adapter.name, &adapter.warnings);
             }
         }
-        Commands::Change { interface, mac } => {
-            let mac = match mac {
-                Some(mac) => *mac,
-                None => generate_random_mac(),
+        Commands::Lookup { mac } => {
+            match oui_registry()?.lookup(*mac) {
+                Some(vendor) => println!(
+                    "{} belongs to {} ({} {})",
+                    mac,
+                    vendor.name,
+                    vendor.block,
+                    vendor.prefix_string()
+                ),
+                None => println!("No vendor found for {}", mac),
+            }
+            if let Some(class) = HardwareAddr::from(*mac).class() {
+                println!("Address type: {}", class);
+            }
+        }
+        Commands::Vendors { command, list } => {
+            let registry = oui_registry()?;
+            let vendors = match command {
+                Some(VendorsCommands::Search { pattern, regex }) if *regex => {
+                    registry.search_regex(pattern)?
+                }
+                Some(VendorsCommands::Search { pattern, .. }) => registry.search(pattern),
+                None if *list => registry.vendors().iter().collect(),
+                None => vec![],
+            };
+            println!("Found {} vendor prefixes", vendors.len());
+            for vendor in vendors {
+                println!(
+                    "{:<14} {}  {}",
+                    vendor.prefix_string(),
+                    vendor.block,
+                    vendor.name
+                );
+            }
+        }
+        Commands::ImportOui { files } => {
+            let count = import_oui_database(files)?;
+            println!("Imported {} OUI assignments", count);
+        }
+        Commands::Change {
+            interface,
+            mac,
+            keep_vendor,
+            same_kind,
+            any_kind,
+            slap,
+            prefix,
+            rng,
+            seed,
+            change_args,
+            output_args,
+        } => {
+            let mut generator: Box<dyn MacGenerator> = match (rng, seed) {
+                (Some(RngKind::Os), Some(_)) => bail!("--seed can only be used with --rng seeded"),
+                (_, Some(seed)) => Box::new(SeededGenerator::new(*seed)),
+                (_, None) => Box::new(OsGenerator),
+            };
+            let generate_options = GenerateOptions {
+                quadrant: slap.map(SlapQuadrant::from),
+                pattern: *prefix,
+            };
+            let mut generate = || {
+                generate_unused_mac(|| {
+                    if *keep_vendor {
+                        generate_vendor_preserving_mac(interface.clone(), generator.as_mut())
+                    } else if *same_kind {
+                        generate_same_kind_mac(interface.clone(), generator.as_mut())
+                    } else if *any_kind {
+                        generate_random_vendor_mac(None, generator.as_mut())
+                    } else {
+                        Ok(generate_mac(generator.as_mut(), &generate_options))
+                    }
+                })
+            };
+            let options = ChangeOptions::from(change_args);
+            let (current_mac, retries, probe_retries) = match mac {
+                Some(mac) => (
+                    do_change_mac_with_options(*mac, interface.clone(), &options)?,
+                    None,
+                    None,
+                ),
+                None => {
+                    let (mut retries, mut probe_retries) = (0, 0);
+                    loop {
+                        let generated = generate()?;
+                        retries += generated.retries;
+                        // A probe found another host using the address, so try another one
+                        match do_change_mac_with_options(generated.mac, interface.clone(), &options)
+                        {
+                            Err(MacchangerError::AddressInUse(_))
+                                if probe_retries < MAX_PROBE_RETRIES =>
+                            {
+                                probe_retries += 1
+                            }
+                            result => {
+                                break (
+                                    result?,
+                                    Some(retries),
+                                    options.probe.then_some(probe_retries),
+                                )
+                            }
+                        }
+                    }
+                }
+            };
+            let report = ChangeReport {
+                interface: interface.clone(),
+                mac: current_mac.to_string(),
+                source: None,
+                retries,
+                probe_retries,
+            };
+            print_change_report(&report, output_args)?;
+        }
+        Commands::Restore {
+            interface,
+            change_args,
+            output_args,
+        } => {
+            let original = discover_hardware_mac(interface.clone())?;
+            if output_args.output.is_none() {
+                println!(
+                    "Found original MAC address of {} (from {})",
+                    original.mac, original.source
+                );
+            }
+            let options = ChangeOptions::from(change_args);
+            let current_mac =
+                do_change_hardware_address(&original.mac, interface.clone(), &options)?;
+            let report = ChangeReport {
+                interface: interface.clone(),
+                mac: current_mac.to_string(),
+                source: Some(original.source),
+                retries: None,
+                probe_retries: None,
             };
-            let current_mac = do_change_mac(mac, interface.clone())?;
+            print_change_report(&report, output_args)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_change_report(report: &ChangeReport, output_args: &OutputArgs) -> Result<()> {
+    let Some(format) = output_args.output else {
+        println!(
+            "Successfully changed MAC address of interface {} to {}",
+            report.interface, report.mac
+        );
+        if let Some(retries) = report.retries.filter(|retries| *retries > 0) {
             println!(
-                "Successfully changed MAC address of interface {} to {}",
-                interface, current_mac
+                "Retried {} time(s) because the generated address was already in use",
+                retries
             );
         }
-        Commands::Restore { interface } => {
-            let original_mac = retrieve_hardware_mac(interface.clone())?;
-            println!("Found original (hardware) MAC address of {}", original_mac);
-            let current_mac = do_change_mac(original_mac, interface.clone())?;
+        if let Some(retries) = report.probe_retries.filter(|retries| *retries > 0) {
             println!(
-                "Successfully changed MAC address of interface {} to {}",
-                interface, current_mac
+                "Retried {} time(s) because another host on the link was using the generated address",
+                retries
             );
         }
+        return Ok(());
+    };
+    let mut header = vec!["interface", "mac"];
+    let mut row = vec![report.interface.clone(), report.mac.clone()];
+    if let Some(source) = report.source {
+        header.push("source");
+        row.push(source.to_string());
+    }
+    if let Some(retries) = report.retries {
+        header.push("retries");
+        row.push(retries.to_string());
     }
+    if let Some(probe_retries) = report.probe_retries {
+        header.push("probe_retries");
+        row.push(probe_retries.to_string());
+    }
+    let mut table = Table::new(header);
+    table.row(row);
+    output::print(format, report, table)
+}
 
-    Ok(())
+fn print_warnings(name: &str, warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {}: {}", name, warning);
+    }
+}
+
+fn print_interface_warnings(interface: &Interface) {
+    print_warnings(&interface.name, &interface.warnings);
+    print_warnings(&interface.name, &interface.adapter.warnings);
+}
+
+/// The names of the IFF_* flags set in `flags`, as shown by `ip link`.
+fn flag_names(flags: u32) -> Vec<&'static str> {
+    const FLAGS: [(u32, &str); 10] = [
+        (0x1, "UP"),
+        (0x2, "BROADCAST"),
+        (0x8, "LOOPBACK"),
+        (0x10, "POINTOPOINT"),
+        (0x40, "RUNNING"),
+        (0x80, "NOARP"),
+        (0x100, "PROMISC"),
+        (0x1000, "MULTICAST"),
+        (0x10000, "LOWER_UP"),
+        (0x20000, "DORMANT"),
+    ];
+    FLAGS
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
 }
@@ -0,0 +1,498 @@
+use crate::os::LinuxMacchangerError;
+use nix::{
+    errno::Errno,
+    libc::{
+        self, IFLA_ADDRESS, IFLA_IFNAME, IFLA_PERM_ADDRESS, NDA_DST, NDA_LLADDR, NETLINK_CAP_ACK,
+        NETLINK_EXT_ACK, NLMSG_DONE, NLMSG_ERROR, NLM_F_ACK, NLM_F_DUMP, NLM_F_REQUEST,
+        RTM_GETLINK, RTM_GETNEIGH, RTM_NEWLINK, SOL_NETLINK,
+    },
+    sys::socket::{
+        bind, recv, sendto, socket, AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol,
+        SockType,
+    },
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::fd::{AsRawFd, OwnedFd},
+};
+
+// Not (yet) exported by the libc crate, see include/uapi/linux/netlink.h
+const NLM_F_CAPPED: u16 = 0x100;
+const NLM_F_ACK_TLVS: u16 = 0x200;
+const NLMSGERR_ATTR_MSG: u16 = 1;
+
+const NLMSG_HDRLEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const NDMSG_LEN: usize = 12;
+const RECEIVE_BUFFER_SIZE: usize = 32 * 1024;
+
+/// A network link as reported by `RTM_GETLINK`.
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub name: String,
+    /// The ARPHRD_* type of the link
+    pub link_type: u16,
+    /// The IFF_* flags
+    pub flags: u32,
+    pub address: Option<Vec<u8>>,
+    /// The address burned into the device, only reported by kernel 5.6 and later
+    pub permanent_address: Option<Vec<u8>>,
+}
+
+/// An entry of the neighbor (ARP/NDP) table as reported by `RTM_GETNEIGH`.
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    /// The index of the interface the neighbor was seen on
+    pub index: u32,
+    /// The network address of the neighbor
+    pub destination: Option<IpAddr>,
+    /// The NUD_* state of the entry
+    pub state: u16,
+    /// The link-layer address of the neighbor, missing for unresolved entries
+    pub address: Option<Vec<u8>>,
+}
+
+/// A rtnetlink (`NETLINK_ROUTE`) socket, used to read and configure links.
+pub struct NetlinkSocket {
+    fd: OwnedFd,
+    sequence: u32,
+}
+
+impl NetlinkSocket {
+    pub fn open() -> Result<Self, LinuxMacchangerError> {
+        let fd = socket(
+            AddressFamily::Netlink,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkRoute,
+        )
+        .map_err(LinuxMacchangerError::NetlinkUnavailable)?;
+        bind(fd.as_raw_fd(), &NetlinkAddr::new(0, 0))
+            .map_err(LinuxMacchangerError::NetlinkUnavailable)?;
+
+        // Ask the kernel for extended ACK messages, without echoing our request back. Older
+        // kernels don't support these, in which case we simply get bare error codes.
+        for option in [NETLINK_EXT_ACK, NETLINK_CAP_ACK] {
+            let enable: libc::c_int = 1;
+            let _ = unsafe {
+                libc::setsockopt(
+                    fd.as_raw_fd(),
+                    SOL_NETLINK,
+                    option,
+                    &enable as *const libc::c_int as *const libc::c_void,
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+        }
+
+        Ok(NetlinkSocket { fd, sequence: 0 })
+    }
+
+    /// Retrieves a single link by its interface index.
+    pub fn get_link(&mut self, index: u32) -> Result<Link, LinuxMacchangerError> {
+        let request = self.link_request(RTM_GETLINK, 0, index, 0, 0, &[]);
+        let mut links = self.transact(&request)?;
+        links
+            .pop()
+            .and_then(|payload| parse_link(&payload))
+            .ok_or(LinuxMacchangerError::NetlinkMessage)
+    }
+
+    /// Dumps the neighbor tables of all address families and interfaces.
+    pub fn get_neighbors(&mut self) -> Result<Vec<Neighbor>, LinuxMacchangerError> {
+        // struct ndmsg, all zero to match every family and interface
+        let request = self.request(RTM_GETNEIGH, NLM_F_DUMP as u16, &[0; NDMSG_LEN], &[]);
+        let neighbors = self.transact(&request)?;
+        Ok(neighbors
+            .iter()
+            .filter_map(|payload| parse_neighbor(payload))
+            .collect())
+    }
+
+    /// Sets the hardware address (`IFLA_ADDRESS`) of a link.
+    pub fn set_address(&mut self, index: u32, address: &[u8]) -> Result<(), LinuxMacchangerError> {
+        let request = self.link_request(RTM_NEWLINK, 0, index, 0, 0, &[(IFLA_ADDRESS, address)]);
+        self.transact(&request).map(|_| ())
+    }
+
+    /// Changes the IFF_* flags selected by `mask` to the values in `flags`.
+    pub fn set_flags(
+        &mut self,
+        index: u32,
+        flags: u32,
+        mask: u32,
+    ) -> Result<(), LinuxMacchangerError> {
+        let request = self.link_request(RTM_NEWLINK, 0, index, flags, mask, &[]);
+        self.transact(&request).map(|_| ())
+    }
+
+    fn link_request(
+        &mut self,
+        message_type: u16,
+        flags: u16,
+        index: u32,
+        ifi_flags: u32,
+        ifi_change: u32,
+        attributes: &[(u16, &[u8])],
+    ) -> Vec<u8> {
+        let header = link_header(index, ifi_flags, ifi_change);
+        self.request(message_type, flags, &header, attributes)
+    }
+
+    fn request(
+        &mut self,
+        message_type: u16,
+        flags: u16,
+        header: &[u8],
+        attributes: &[(u16, &[u8])],
+    ) -> Vec<u8> {
+        self.sequence += 1;
+        build_request(self.sequence, message_type, flags, header, attributes)
+    }
+
+    /// Sends the request and collects the payloads of all replies until the kernel
+    /// acknowledges the request, finishes the dump or reports an error.
+    fn transact(&mut self, request: &[u8]) -> Result<Vec<Vec<u8>>, LinuxMacchangerError> {
+        sendto(
+            self.fd.as_raw_fd(),
+            request,
+            &NetlinkAddr::new(0, 0),
+            MsgFlags::empty(),
+        )
+        .map_err(LinuxMacchangerError::NetlinkSocket)?;
+
+        let mut payloads = vec![];
+        let mut buffer = vec![0u8; RECEIVE_BUFFER_SIZE];
+        loop {
+            let length = recv(self.fd.as_raw_fd(), &mut buffer, MsgFlags::empty())
+                .map_err(LinuxMacchangerError::NetlinkSocket)?;
+            let mut data = &buffer[..length];
+
+            while data.len() >= NLMSG_HDRLEN {
+                let message_length = u32::from_ne_bytes(data[0..4].try_into().unwrap()) as usize;
+                let message_type = u16::from_ne_bytes(data[4..6].try_into().unwrap());
+                let flags = u16::from_ne_bytes(data[6..8].try_into().unwrap());
+                let sequence = u32::from_ne_bytes(data[8..12].try_into().unwrap());
+                if message_length < NLMSG_HDRLEN || message_length > data.len() {
+                    return Err(LinuxMacchangerError::NetlinkMessage);
+                }
+                let payload = &data[NLMSG_HDRLEN..message_length];
+                data = &data[align(message_length).min(data.len())..];
+
+                if sequence != self.sequence {
+                    continue;
+                }
+                match message_type as i32 {
+                    NLMSG_DONE => return Ok(payloads),
+                    NLMSG_ERROR => {
+                        return match parse_error(payload)? {
+                            0 => Ok(payloads),
+                            errno => Err(LinuxMacchangerError::Netlink {
+                                errno: Errno::from_raw(-errno),
+                                message: parse_extended_ack(payload, flags),
+                            }),
+                        }
+                    }
+                    _ => payloads.push(payload.to_vec()),
+                }
+            }
+        }
+    }
+}
+
+/// Builds a `struct ifinfomsg` selecting the link by its index.
+fn link_header(index: u32, ifi_flags: u32, ifi_change: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(IFINFOMSG_LEN);
+    header.push(libc::AF_UNSPEC as u8);
+    header.push(0);
+    header.extend_from_slice(&0u16.to_ne_bytes());
+    header.extend_from_slice(&(index as i32).to_ne_bytes());
+    header.extend_from_slice(&ifi_flags.to_ne_bytes());
+    header.extend_from_slice(&ifi_change.to_ne_bytes());
+    header
+}
+
+/// Builds a request from the family specific header (e.g. `struct ifinfomsg`) and the
+/// attributes.
+fn build_request(
+    sequence: u32,
+    message_type: u16,
+    flags: u16,
+    header: &[u8],
+    attributes: &[(u16, &[u8])],
+) -> Vec<u8> {
+    let flags = flags | NLM_F_REQUEST as u16 | NLM_F_ACK as u16;
+
+    let mut request = Vec::with_capacity(NLMSG_HDRLEN + header.len());
+    // struct nlmsghdr, the length is filled in at the end
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(&message_type.to_ne_bytes());
+    request.extend_from_slice(&flags.to_ne_bytes());
+    request.extend_from_slice(&sequence.to_ne_bytes());
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(header);
+
+    for (attribute_type, data) in attributes {
+        // struct rtattr followed by the data, padded to 4 bytes
+        request.extend_from_slice(&((4 + data.len()) as u16).to_ne_bytes());
+        request.extend_from_slice(&attribute_type.to_ne_bytes());
+        request.extend_from_slice(data);
+        request.resize(align(request.len()), 0);
+    }
+
+    let length = request.len() as u32;
+    request[0..4].copy_from_slice(&length.to_ne_bytes());
+    request
+}
+
+fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// Iterates over the `struct rtattr` attributes in the data as (type, value) pairs.
+fn attributes(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 4 {
+            return None;
+        }
+        let length = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let attribute_type = u16::from_ne_bytes([data[2], data[3]]);
+        if length < 4 || length > data.len() {
+            return None;
+        }
+        let value = &data[4..length];
+        data = &data[align(length).min(data.len())..];
+        Some((attribute_type, value))
+    })
+}
+
+fn parse_link(payload: &[u8]) -> Option<Link> {
+    if payload.len() < IFINFOMSG_LEN {
+        return None;
+    }
+    let mut link = Link {
+        flags: u32::from_ne_bytes(payload[8..12].try_into().ok()?),
+        link_type: u16::from_ne_bytes(payload[2..4].try_into().ok()?),
+        name: String::new(),
+        address: None,
+        permanent_address: None,
+    };
+    for (attribute_type, value) in attributes(&payload[IFINFOMSG_LEN..]) {
+        match attribute_type {
+            IFLA_IFNAME => {
+                link.name = String::from_utf8_lossy(value)
+                    .trim_end_matches('\0')
+                    .to_owned()
+            }
+            IFLA_ADDRESS => link.address = Some(value.to_vec()),
+            IFLA_PERM_ADDRESS => link.permanent_address = Some(value.to_vec()),
+            _ => {}
+        }
+    }
+    Some(link)
+}
+
+fn parse_neighbor(payload: &[u8]) -> Option<Neighbor> {
+    if payload.len() < NDMSG_LEN {
+        return None;
+    }
+    let mut neighbor = Neighbor {
+        index: i32::from_ne_bytes(payload[4..8].try_into().ok()?) as u32,
+        destination: None,
+        state: u16::from_ne_bytes(payload[8..10].try_into().ok()?),
+        address: None,
+    };
+    for (attribute_type, value) in attributes(&payload[NDMSG_LEN..]) {
+        match attribute_type {
+            NDA_DST => {
+                neighbor.destination = match value.len() {
+                    4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(value).ok()?))),
+                    16 => Some(IpAddr::V6(Ipv6Addr::from(
+                        <[u8; 16]>::try_from(value).ok()?,
+                    ))),
+                    _ => None,
+                }
+            }
+            NDA_LLADDR => neighbor.address = Some(value.to_vec()),
+            _ => {}
+        }
+    }
+    Some(neighbor)
+}
+
+/// Returns the (negative) error code of a `struct nlmsgerr`, 0 for an ACK.
+fn parse_error(payload: &[u8]) -> Result<i32, LinuxMacchangerError> {
+    payload
+        .get(0..4)
+        .map(|b| i32::from_ne_bytes(b.try_into().unwrap()))
+        .ok_or(LinuxMacchangerError::NetlinkMessage)
+}
+
+/// Extracts the human readable message from the extended ACK attributes, which follow the
+/// error code and the (possibly capped) original request.
+fn parse_extended_ack(payload: &[u8], flags: u16) -> Option<String> {
+    if flags & NLM_F_ACK_TLVS == 0 {
+        return None;
+    }
+    let request = payload.get(4..)?;
+    let request_length = if flags & NLM_F_CAPPED != 0 {
+        NLMSG_HDRLEN
+    } else {
+        u32::from_ne_bytes(request.get(0..4)?.try_into().ok()?) as usize
+    };
+    attributes(request.get(align(request_length)..)?)
+        .find(|(attribute_type, _)| *attribute_type == NLMSGERR_ATTR_MSG)
+        .map(|(_, value)| {
+            String::from_utf8_lossy(value)
+                .trim_end_matches('\0')
+                .to_owned()
+        })
+}
+
+// The captured messages are in the byte order of the x86-64 machine they were taken on
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+    use nix::libc::IFLA_MTU;
+
+    /// `RTM_NEWLINK` for eth0, trimmed to IFLA_IFNAME, IFLA_MTU, IFLA_ADDRESS, IFLA_BROADCAST
+    /// and IFLA_PERM_ADDRESS
+    const NEWLINK: &str = "0000010004000000431001000000000009000300657468300000000008000400780500000a00010002fc0000000100000a000200ffffffffffff00000a00360002fc000000010000";
+    /// `RTM_NEWNEIGH` for the IGMP multicast group on interface 4
+    const NEWNEIGH: &str = "02000000040000004000000508000100e00000160a00020001005e0000160000080004000000000014000300d7dc050067c5050067c5050000000000";
+    /// `NLMSG_ERROR` for setting an MTU of 10, with the request and an extended ACK message
+    const EXTACK: &str = "eaffffff2800000010000500010000000000000000000000060000000000000000000000080004000a000000210001006d7475206c657373207468616e20646576696365206d696e696d756d00000000";
+    /// The same error with NETLINK_CAP_ACK, which leaves out the request's payload
+    const EXTACK_CAPPED: &str = "eaffffff28000000100005000100000000000000210001006d7475206c657373207468616e20646576696365206d696e696d756d00000000";
+    /// `NLMSG_ERROR` without an extended ACK message
+    const ERROR: &str =
+        "eaffffff28000000100005000100000000000000000000000600000000000000000000000600010002000000";
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        hex::decode(hex).unwrap()
+    }
+
+    #[test]
+    fn builds_link_request() {
+        let request = build_request(
+            1,
+            RTM_NEWLINK,
+            0,
+            &link_header(4, 0, 0),
+            &[(IFLA_ADDRESS, &[0x02, 0, 0, 0, 0, 0xBB])],
+        );
+        assert_eq!(
+            request,
+            bytes(concat!(
+                // nlmsghdr: length, RTM_NEWLINK, NLM_F_REQUEST | NLM_F_ACK, sequence, port
+                "2c000000100005000100000000000000",
+                // ifinfomsg: AF_UNSPEC, index 4, no flags to change
+                "00000000040000000000000000000000",
+                // IFLA_ADDRESS, padded to 4 bytes
+                "0a0001000200000000bb0000",
+            ))
+        );
+    }
+
+    #[test]
+    fn pads_every_attribute() {
+        let request = build_request(
+            7,
+            RTM_GETNEIGH,
+            NLM_F_DUMP as u16,
+            &[0; NDMSG_LEN],
+            &[(IFLA_IFNAME, b"pa\0"), (IFLA_MTU, &1500u32.to_ne_bytes())],
+        );
+        assert_eq!(request.len(), NLMSG_HDRLEN + NDMSG_LEN + 8 + 8);
+        assert_eq!(&request[0..4], &(request.len() as u32).to_ne_bytes());
+        assert_eq!(&request[6..8], &0x305u16.to_ne_bytes());
+        assert_eq!(&request[8..12], &7u32.to_ne_bytes());
+        assert_eq!(
+            &request[NLMSG_HDRLEN + NDMSG_LEN..],
+            bytes("070003007061000008000400dc050000")
+        );
+        let attributes: Vec<_> = attributes(&request[NLMSG_HDRLEN + NDMSG_LEN..]).collect();
+        assert_eq!(
+            attributes,
+            [
+                (IFLA_IFNAME, &b"pa\0"[..]),
+                (IFLA_MTU, &1500u32.to_ne_bytes()[..])
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_malformed_attributes() {
+        // A length shorter than the rtattr header itself
+        assert_eq!(attributes(&bytes("0200030070610000")).count(), 0);
+        // A length beyond the end of the data
+        assert_eq!(attributes(&bytes("0800010002fc")).count(), 0);
+        // The valid attributes before a broken one are still returned
+        assert_eq!(attributes(&bytes("080004007805000020000100")).count(), 1);
+    }
+
+    #[test]
+    fn parses_link() {
+        let link = parse_link(&bytes(NEWLINK)).unwrap();
+        assert_eq!(link.name, "eth0");
+        assert_eq!(link.link_type, 1);
+        assert_eq!(link.flags, 0x11043);
+        assert_eq!(link.address, Some(bytes("02fc00000001")));
+        assert_eq!(link.permanent_address, Some(bytes("02fc00000001")));
+    }
+
+    #[test]
+    fn parses_truncated_link() {
+        // Cut off in the middle of IFLA_ADDRESS
+        let link = parse_link(&bytes(&NEWLINK[..2 * 40])).unwrap();
+        assert_eq!(link.name, "eth0");
+        assert_eq!(link.address, None);
+        assert_eq!(link.permanent_address, None);
+
+        assert!(parse_link(&bytes(&NEWLINK[..2 * (IFINFOMSG_LEN - 1)])).is_none());
+    }
+
+    #[test]
+    fn parses_neighbor() {
+        let neighbor = parse_neighbor(&bytes(NEWNEIGH)).unwrap();
+        assert_eq!(neighbor.index, 4);
+        assert_eq!(
+            neighbor.destination,
+            Some(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 22)))
+        );
+        assert_eq!(neighbor.state, 0x40);
+        assert_eq!(neighbor.address, Some(bytes("01005e000016")));
+    }
+
+    #[test]
+    fn parses_errors() {
+        assert_eq!(parse_error(&bytes(ERROR)).unwrap(), -22);
+        assert_eq!(parse_error(&bytes(EXTACK)).unwrap(), -22);
+        assert_eq!(parse_error(&bytes("00000000")).unwrap(), 0);
+        assert!(parse_error(&bytes("eaff")).is_err());
+    }
+
+    #[test]
+    fn parses_extended_ack() {
+        let message = Some("mtu less than device minimum".to_owned());
+        assert_eq!(parse_extended_ack(&bytes(EXTACK), NLM_F_ACK_TLVS), message);
+        assert_eq!(
+            parse_extended_ack(&bytes(EXTACK_CAPPED), NLM_F_ACK_TLVS | NLM_F_CAPPED),
+            message
+        );
+        assert_eq!(parse_extended_ack(&bytes(ERROR), 0), None);
+        // Without NLM_F_ACK_TLVS the bytes after the request aren't attributes
+        assert_eq!(parse_extended_ack(&bytes(EXTACK), 0), None);
+    }
+
+    #[test]
+    fn ignores_truncated_extended_ack() {
+        // Cut off in the middle of the message attribute
+        let truncated = bytes(&EXTACK[..EXTACK.len() - 20]);
+        assert_eq!(parse_extended_ack(&truncated, NLM_F_ACK_TLVS), None);
+        // Cut off in the middle of the echoed request
+        let truncated = bytes(&EXTACK[..2 * 30]);
+        assert_eq!(parse_extended_ack(&truncated, NLM_F_ACK_TLVS), None);
+        assert_eq!(parse_extended_ack(&bytes("eaffff"), NLM_F_ACK_TLVS), None);
+    }
+}
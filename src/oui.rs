@@ -0,0 +1,652 @@
+use crate::{generator::GeneratorRng, AdapterKind, MacGenerator, MacchangerError};
+use macaddr::MacAddr;
+use oui_data::{Registry, OUI_ENTRIES};
+use rand::seq::IteratorRandom;
+use regex::RegexBuilder;
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const CACHE_MAGIC: &[u8; 6] = b"MCOUI\x01";
+
+/// Name fragments of vendors known for wireless chipsets and cards, used to tell which
+/// assignments belong to wireless adapters since the IEEE data carries no device class. This
+/// is a coarse approximation: vendors making both, like Intel, Realtek, Broadcom or Qualcomm,
+/// don't say which of their OUIs went to wireless adapters, so they all count as wired.
+const WIRELESS_VENDOR_KEYWORDS: &[&str] = &[
+    "wireless",
+    "wi-fi",
+    "wifi",
+    "wlan",
+    "atheros",
+    "ralink",
+    "mediatek",
+    "airgo",
+    "aironet",
+    "azurewave",
+    "murata",
+    "edimax",
+    "ubiquiti",
+    "ruckus",
+    "aruba",
+    "meraki",
+];
+
+/// The size of an IEEE assignment block, which determines how many leading bits of a MAC
+/// address identify the vendor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockSize {
+    /// MA-L: a 24-bit prefix (the classic OUI)
+    MaL,
+    /// MA-M: a 28-bit prefix
+    MaM,
+    /// MA-S: a 36-bit prefix (including the legacy IAB assignments)
+    MaS,
+}
+
+impl BlockSize {
+    fn from_prefix_bits(bits: u32) -> Option<Self> {
+        match bits {
+            24 => Some(BlockSize::MaL),
+            28 => Some(BlockSize::MaM),
+            36 => Some(BlockSize::MaS),
+            _ => None,
+        }
+    }
+
+    /// Number of leading bits of the MAC address that belong to the assignment.
+    pub fn prefix_bits(&self) -> u32 {
+        match self {
+            BlockSize::MaL => 24,
+            BlockSize::MaM => 28,
+            BlockSize::MaS => 36,
+        }
+    }
+}
+
+impl Display for BlockSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockSize::MaL => write!(f, "MA-L"),
+            BlockSize::MaM => write!(f, "MA-M"),
+            BlockSize::MaS => write!(f, "MA-S"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vendor {
+    /// The assigned prefix, right-aligned (e.g. `0x001B21` for an MA-L block)
+    pub prefix: u64,
+    pub block: BlockSize,
+    pub name: String,
+}
+
+impl Vendor {
+    /// Formats the prefix as hex digits separated by colons, e.g. `00:1B:21` or `8C:1F:64:AF:A`.
+    pub fn prefix_string(&self) -> String {
+        let digits = (self.block.prefix_bits() / 4) as usize;
+        let hex = format!("{:0width$X}", self.prefix, width = digits);
+        hex.as_bytes()
+            .chunks(2)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<&str>>()
+            .join(":")
+    }
+
+    /// The kind of adapter this vendor is known for, based on its name. Only vendors with an
+    /// obviously wireless name are classified as wireless, see `WIRELESS_VENDOR_KEYWORDS`.
+    pub fn kind(&self) -> AdapterKind {
+        let name = self.name.to_lowercase();
+        if WIRELESS_VENDOR_KEYWORDS.iter().any(|k| name.contains(k)) {
+            AdapterKind::Wireless
+        } else {
+            AdapterKind::Wired
+        }
+    }
+
+    /// Checks if the assignment can be used for a regular unicast address, i.e. it belongs to a
+    /// named vendor and doesn't have the multicast or locally administered bit set.
+    pub fn is_assignable(&self) -> bool {
+        let first_octet = (self.prefix >> (self.block.prefix_bits() - 8)) as u8;
+        first_octet & 0x03 == 0 && !self.name.eq_ignore_ascii_case("private")
+    }
+
+    /// Checks if the given MAC address falls inside this vendor's assignment.
+    pub fn contains(&self, mac: MacAddr) -> bool {
+        mac_to_u64(mac) >> (48 - self.block.prefix_bits()) == self.prefix
+    }
+}
+
+/// In-memory registry of IEEE assignments.
+#[derive(Debug, Clone, Default)]
+pub struct OuiRegistry {
+    vendors: Vec<Vendor>,
+    index: HashMap<(BlockSize, u64), usize>,
+}
+
+impl OuiRegistry {
+    /// Builds the registry from the IEEE data embedded in this build.
+    pub fn embedded() -> Self {
+        let vendors = OUI_ENTRIES
+            .values()
+            .filter_map(|entry| {
+                let block = match entry.registry() {
+                    Registry::Mal => BlockSize::MaL,
+                    Registry::Mam => BlockSize::MaM,
+                    Registry::Mas | Registry::Iab => BlockSize::MaS,
+                    Registry::Cid => return None,
+                };
+                let prefix = u64::from_str_radix(entry.oui(), 16).ok()?;
+                Some(Vendor {
+                    prefix,
+                    block,
+                    name: entry.organization().to_owned(),
+                })
+            })
+            .collect();
+        OuiRegistry::from_vendors(vendors)
+    }
+
+    /// Builds the registry from a locally downloaded IEEE `oui.txt`/`oui.csv` (or the MA-M and
+    /// MA-S equivalents) or a Wireshark `manuf` file. The format is detected from the contents.
+    pub fn from_file(path: &Path) -> Result<Self, MacchangerError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| MacchangerError::OuiImportError(format!("{}: {}", path.display(), e)))?;
+        let first_line = contents.lines().find(|l| !l.trim().is_empty());
+        let vendors = if first_line.is_some_and(|l| l.starts_with("Registry,")) {
+            parse_ieee_csv(&contents)
+        } else if contents.contains("(hex)") {
+            parse_ieee_txt(&contents)
+        } else {
+            parse_manuf(&contents)
+        };
+
+        if vendors.is_empty() {
+            return Err(MacchangerError::OuiImportError(format!(
+                "{}: no OUI assignments found",
+                path.display()
+            )));
+        }
+        Ok(OuiRegistry::from_vendors(vendors))
+    }
+
+    fn from_vendors(mut vendors: Vec<Vendor>) -> Self {
+        vendors.sort_by_key(|v| {
+            (
+                v.prefix << (48 - v.block.prefix_bits()),
+                v.block.prefix_bits(),
+            )
+        });
+        vendors.dedup_by_key(|v| (v.block, v.prefix));
+        let index = vendors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| ((v.block, v.prefix), i))
+            .collect();
+        OuiRegistry { vendors, index }
+    }
+
+    /// Adds all assignments of `other`, replacing the ones with the same prefix.
+    pub fn merge(self, other: OuiRegistry) -> Self {
+        let mut vendors = other.vendors;
+        vendors.extend(self.vendors);
+        OuiRegistry::from_vendors(vendors)
+    }
+
+    /// Reads a registry from the compact binary index written by [`OuiRegistry::write_cache`].
+    pub fn read_cache(path: &Path) -> Result<Self, MacchangerError> {
+        let invalid =
+            || MacchangerError::OuiCacheError(format!("{}: invalid index", path.display()));
+        let data = fs::read(path)
+            .map_err(|e| MacchangerError::OuiCacheError(format!("{}: {}", path.display(), e)))?;
+        let mut data = data
+            .strip_prefix(CACHE_MAGIC.as_slice())
+            .ok_or_else(invalid)?;
+
+        let mut vendors = vec![];
+        while !data.is_empty() {
+            if data.len() < 8 {
+                return Err(invalid());
+            }
+            let block = BlockSize::from_prefix_bits(data[0] as u32).ok_or_else(invalid)?;
+            let prefix = data[1..6]
+                .iter()
+                .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            let name_len = u16::from_le_bytes([data[6], data[7]]) as usize;
+            let name = data.get(8..8 + name_len).ok_or_else(invalid)?;
+            vendors.push(Vendor {
+                prefix,
+                block,
+                name: String::from_utf8_lossy(name).into_owned(),
+            });
+            data = &data[8 + name_len..];
+        }
+        Ok(OuiRegistry::from_vendors(vendors))
+    }
+
+    /// Writes the registry as a compact binary index: per assignment one byte with the prefix
+    /// length, five bytes of prefix, a little endian `u16` name length and the name itself.
+    pub fn write_cache(&self, path: &Path) -> Result<(), MacchangerError> {
+        let cache_error = |e: std::io::Error| {
+            MacchangerError::OuiCacheError(format!("{}: {}", path.display(), e))
+        };
+        let mut data = CACHE_MAGIC.to_vec();
+        for vendor in &self.vendors {
+            let name = &vendor.name.as_bytes()[..vendor.name.len().min(u16::MAX as usize)];
+            data.push(vendor.block.prefix_bits() as u8);
+            data.extend_from_slice(&vendor.prefix.to_be_bytes()[3..]);
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(name);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(cache_error)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&temp_path).map_err(cache_error)?;
+        file.write_all(&data).map_err(cache_error)?;
+        file.sync_all().map_err(cache_error)?;
+        fs::rename(&temp_path, path).map_err(cache_error)
+    }
+
+    /// Finds the most specific assignment (MA-S, then MA-M, then MA-L) containing the MAC address.
+    pub fn lookup(&self, mac: MacAddr) -> Option<&Vendor> {
+        let mac = mac_to_u64(mac);
+        [BlockSize::MaS, BlockSize::MaM, BlockSize::MaL]
+            .iter()
+            .find_map(|block| {
+                let prefix = mac >> (48 - block.prefix_bits());
+                self.index.get(&(*block, prefix))
+            })
+            .map(|i| &self.vendors[*i])
+    }
+
+    /// Picks a random assignable vendor. Virtual adapters have no vendor class of their own,
+    /// so they are matched with wired vendors.
+    pub fn random_vendor(
+        &self,
+        kind: Option<AdapterKind>,
+        generator: &mut dyn MacGenerator,
+    ) -> Option<&Vendor> {
+        let kind = kind.map(|k| match k {
+            AdapterKind::Virtual => AdapterKind::Wired,
+            k => k,
+        });
+        self.vendors
+            .iter()
+            .filter(|v| v.is_assignable() && kind.is_none_or(|k| v.kind() == k))
+            .choose(&mut GeneratorRng(generator))
+    }
+
+    /// Finds all assignments whose vendor name or prefix contains the pattern, ignoring case.
+    /// Prefixes match with any or no separators, `001B21`, `00-1B-21` and `00:1B:21` alike.
+    pub fn search(&self, pattern: &str) -> Vec<&Vendor> {
+        let pattern = pattern.to_lowercase();
+        let digits = strip_separators(&pattern);
+        self.vendors
+            .iter()
+            .filter(|v| {
+                v.name.to_lowercase().contains(&pattern)
+                    || (!digits.is_empty()
+                        && strip_separators(&v.prefix_string().to_lowercase()).contains(&digits))
+            })
+            .collect()
+    }
+
+    /// Finds all assignments whose vendor name matches the regular expression, ignoring case.
+    pub fn search_regex(&self, pattern: &str) -> Result<Vec<&Vendor>, MacchangerError> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| MacchangerError::InvalidSearchPattern(e.to_string()))?;
+        Ok(self
+            .vendors
+            .iter()
+            .filter(|v| regex.is_match(&v.name))
+            .collect())
+    }
+
+    /// All assignments, ordered by prefix.
+    pub fn vendors(&self) -> &[Vendor] {
+        &self.vendors
+    }
+
+    pub fn len(&self) -> usize {
+        self.vendors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vendors.is_empty()
+    }
+}
+
+/// Returns the process-wide OUI registry: the embedded data, updated with the assignments
+/// from the cached index of an imported database if there is one. The registry is loaded once
+/// per process, an unreadable or corrupt index fails every call.
+pub fn registry() -> Result<&'static OuiRegistry, MacchangerError> {
+    static REGISTRY: OnceLock<Result<OuiRegistry, String>> = OnceLock::new();
+    let registry = REGISTRY.get_or_init(|| {
+        let embedded = OuiRegistry::embedded();
+        let Some(path) = cache_path() else {
+            return Ok(embedded);
+        };
+        if let Ok(false) = path.try_exists() {
+            return Ok(embedded);
+        }
+        match OuiRegistry::read_cache(&path) {
+            Ok(imported) => Ok(embedded.merge(imported)),
+            Err(MacchangerError::OuiCacheError(e)) => Err(format!(
+                "{} (import the OUI database again or remove the index)",
+                e
+            )),
+            Err(e) => Err(e.to_string()),
+        }
+    });
+    registry
+        .as_ref()
+        .map_err(|e| MacchangerError::OuiCacheError(e.clone()))
+}
+
+/// Location of the cached index of an imported OUI database, inside the user's cache dir.
+pub fn cache_path() -> Option<PathBuf> {
+    let cache_dir = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    };
+    cache_dir.map(|dir| dir.join("macchanger").join("oui.bin"))
+}
+
+/// Parses the given OUI database files into a single registry and stores it as the cached
+/// index. Only processes started afterwards use it, a registry that was already loaded by
+/// [`registry`] keeps its data. Returns the number of imported assignments.
+pub fn import_oui_database(paths: &[PathBuf]) -> Result<usize, MacchangerError> {
+    let registry = paths
+        .iter()
+        .try_fold(OuiRegistry::default(), |registry, path| {
+            Ok::<_, MacchangerError>(registry.merge(OuiRegistry::from_file(path)?))
+        })?;
+    let cache_path = cache_path().ok_or(MacchangerError::OuiCacheError(
+        "Could not determine the cache directory".to_owned(),
+    ))?;
+    registry.write_cache(&cache_path)?;
+    Ok(registry.len())
+}
+
+/// Parses the IEEE CSV format: `Registry,Assignment,Organization Name,Organization Address`.
+fn parse_ieee_csv(contents: &str) -> Vec<Vendor> {
+    csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents.as_bytes())
+        .records()
+        .filter_map(|record| {
+            let record = record.ok()?;
+            let block = match record.get(0)? {
+                "MA-L" => BlockSize::MaL,
+                "MA-M" => BlockSize::MaM,
+                "MA-S" | "IAB" => BlockSize::MaS,
+                _ => return None,
+            };
+            let prefix = u64::from_str_radix(record.get(1)?.trim(), 16).ok()?;
+            Some(Vendor {
+                prefix,
+                block,
+                name: record.get(2)?.trim().to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Parses the IEEE text format, where every assignment has a `(hex)` line with the OUI and a
+/// `(base 16)` line that holds either the full MA-L prefix or the range inside the OUI.
+fn parse_ieee_txt(contents: &str) -> Vec<Vendor> {
+    let mut vendors = vec![];
+    let mut oui: Option<u64> = None;
+    for line in contents.lines() {
+        if let Some((hex, _)) = line.split_once("(hex)") {
+            oui = u64::from_str_radix(&hex.trim().replace('-', ""), 16).ok();
+        } else if let Some((range, name)) = line.split_once("(base 16)") {
+            let name = name.trim().to_owned();
+            let vendor = match (range.trim().split_once('-'), oui) {
+                (Some((start, end)), Some(oui)) => {
+                    let start = u64::from_str_radix(start, 16).ok();
+                    let end = u64::from_str_radix(end, 16).ok();
+                    start.zip(end).and_then(|(start, end)| {
+                        // The range has to be a whole, aligned block inside the OUI
+                        let size = end.checked_sub(start)? + 1;
+                        if !size.is_power_of_two() || start % size != 0 || end >= 1 << 24 {
+                            return None;
+                        }
+                        let host_bits = size.trailing_zeros();
+                        let block = BlockSize::from_prefix_bits(48 - host_bits)?;
+                        Some(Vendor {
+                            prefix: ((oui << 24) | start) >> host_bits,
+                            block,
+                            name,
+                        })
+                    })
+                }
+                (None, _) => u64::from_str_radix(range.trim(), 16)
+                    .ok()
+                    .map(|prefix| Vendor {
+                        prefix,
+                        block: BlockSize::MaL,
+                        name,
+                    }),
+                _ => None,
+            };
+            vendors.extend(vendor);
+        }
+    }
+    vendors
+}
+
+/// Parses a Wireshark `manuf` file: `<prefix>[/<bits>]<TAB><short name>[<TAB><long name>]`.
+fn parse_manuf(contents: &str) -> Vec<Vendor> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let assignment = fields.next()?.trim();
+            let (address, bits) = match assignment.split_once('/') {
+                Some((address, bits)) => (address, bits.parse::<u32>().ok()?),
+                None => (assignment, 24),
+            };
+            let block = BlockSize::from_prefix_bits(bits)?;
+            let short_name = fields.next()?.trim();
+            let name = fields.next().map(str::trim).unwrap_or(short_name);
+
+            let digits = address.replace([':', '-', '.'], "");
+            let value = u64::from_str_radix(digits.get(..(bits / 4) as usize)?, 16).ok()?;
+            Some(Vendor {
+                prefix: value,
+                block,
+                name: name.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Looks up the vendor the given MAC address was assigned to.
+pub fn lookup_vendor(mac: MacAddr) -> Result<Option<Vendor>, MacchangerError> {
+    Ok(registry()?.lookup(mac).cloned())
+}
+
+/// Removes the separators between the bytes of a (partial) MAC address.
+fn strip_separators(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect()
+}
+
+/// Converts the first six bytes of the MAC address into a 48-bit integer.
+fn mac_to_u64(mac: MacAddr) -> u64 {
+    mac.as_bytes()
+        .iter()
+        .take(6)
+        .fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vendor(prefix: u64, block: BlockSize, name: &str) -> Vendor {
+        Vendor {
+            prefix,
+            block,
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_ieee_txt() {
+        let contents = "\
+OUI/MA-L                                                    Organization
+company_id                                                  Organization
+                                                            Address
+
+00-1B-21   (hex)\t\tIntel Corporate
+001B21     (base 16)\t\tIntel Corporate
+\t\t\t\tLot 8, Jalan Hi-Tech 2/3
+
+70-B3-D5   (hex)\t\tExample Ltd
+B3C000-B3CFFF     (base 16)\t\tExample Ltd
+
+8C-1F-64   (hex)\t\tTiny Devices
+F5A000-F5AFFF     (base 16)\t\tTiny Devices
+F5B000-F5BFFF     (base 16)\t\tTiny Devices 2
+";
+        assert_eq!(
+            parse_ieee_txt(contents),
+            vec![
+                vendor(0x001B21, BlockSize::MaL, "Intel Corporate"),
+                vendor(0x70B3D5B3C, BlockSize::MaS, "Example Ltd"),
+                vendor(0x8C1F64F5A, BlockSize::MaS, "Tiny Devices"),
+                vendor(0x8C1F64F5B, BlockSize::MaS, "Tiny Devices 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_txt_ranges() {
+        let contents = "\
+70-B3-D5   (hex)\t\tReversed
+FFFFFF-000000     (base 16)\t\tReversed
+70-B3-D5   (hex)\t\tUneven
+000000-000FFE     (base 16)\t\tUneven
+70-B3-D5   (hex)\t\tUnaligned
+000800-0017FF     (base 16)\t\tUnaligned
+70-B3-D5   (hex)\t\tMedium
+100000-1FFFFF     (base 16)\t\tMedium
+";
+        assert_eq!(
+            parse_ieee_txt(contents),
+            vec![vendor(0x70B3D51, BlockSize::MaM, "Medium")]
+        );
+    }
+
+    #[test]
+    fn parses_ieee_csv() {
+        let contents = "\
+Registry,Assignment,Organization Name,Organization Address
+MA-L,001B21,Intel Corporate,\"Lot 8, Jalan Hi-Tech 2/3 Kulim  Kedah  MY 09000 \"
+MA-M,70B3D51,\"Quoted, Name\",\"Multi
+line address\"
+MA-S,8C1F64F5A,Tiny Devices,Somewhere
+IAB,0050C2123,Old Block,Elsewhere
+CID,0A1B2C,Company ID,Nowhere
+MA-L,not hex,Broken,Nowhere
+";
+        assert_eq!(
+            parse_ieee_csv(contents),
+            vec![
+                vendor(0x001B21, BlockSize::MaL, "Intel Corporate"),
+                vendor(0x70B3D51, BlockSize::MaM, "Quoted, Name"),
+                vendor(0x8C1F64F5A, BlockSize::MaS, "Tiny Devices"),
+                vendor(0x0050C2123, BlockSize::MaS, "Old Block"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_manuf() {
+        let contents = "\
+# Wireshark manuf file
+00:1B:21\tIntel\tIntel Corporate
+00:50:C2:12:30:00/36\tOldBlock
+70:B3:D5:10:00:00/28\tMedium\tMedium Devices
+00:00:00:00:00:00/12\tUnsupported
+";
+        assert_eq!(
+            parse_manuf(contents),
+            vec![
+                vendor(0x001B21, BlockSize::MaL, "Intel Corporate"),
+                vendor(0x0050C2123, BlockSize::MaS, "OldBlock"),
+                vendor(0x70B3D51, BlockSize::MaM, "Medium Devices"),
+            ]
+        );
+    }
+
+    #[test]
+    fn searches_prefixes_with_any_separator() {
+        let registry = OuiRegistry::from_vendors(vec![
+            vendor(0x001B21, BlockSize::MaL, "Intel Corporate"),
+            vendor(0x70B3D51, BlockSize::MaM, "Medium Devices"),
+        ]);
+        let names = |pattern| {
+            registry
+                .search(pattern)
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        for pattern in ["00:1B:21", "00-1b-21", "001B21", "001b2", "00.1B.21"] {
+            assert_eq!(names(pattern), ["Intel Corporate"], "{}", pattern);
+        }
+        assert_eq!(names("70B3D51"), ["Medium Devices"]);
+        assert_eq!(names("70-B3-D5-1"), ["Medium Devices"]);
+        assert_eq!(names("devices"), ["Medium Devices"]);
+        assert!(names("-").is_empty());
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let registry = OuiRegistry::from_vendors(vec![
+            vendor(0x001B21, BlockSize::MaL, "Intel Corporate"),
+            vendor(0x70B3D51, BlockSize::MaM, "Medium Devices"),
+            vendor(0x8C1F64F5A, BlockSize::MaS, "Tiny Devices ünïcode"),
+        ]);
+        let path = std::env::temp_dir().join(format!("macchanger-oui-{}.idx", std::process::id()));
+        registry.write_cache(&path).unwrap();
+        let read = OuiRegistry::read_cache(&path);
+        fs::remove_file(&path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(read.vendors(), registry.vendors());
+        let mac = MacAddr::from([0x70, 0xB3, 0xD5, 0x1A, 0xBC, 0xDE]);
+        assert_eq!(
+            read.lookup(mac).map(|v| v.name.as_str()),
+            Some("Medium Devices")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_cache() {
+        let path = std::env::temp_dir().join(format!("macchanger-bad-{}.idx", std::process::id()));
+        fs::write(&path, b"MCOUI\x01\x18\x00").unwrap();
+        let read = OuiRegistry::read_cache(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(read, Err(MacchangerError::OuiCacheError(_))));
+    }
+}
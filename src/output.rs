@@ -0,0 +1,69 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Yaml,
+    Table,
+}
+
+/// A flat view of the output of a command, used for the CSV and table formats.
+pub struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: Vec<&'static str>) -> Self {
+        Table {
+            header,
+            rows: vec![],
+        }
+    }
+
+    pub fn row(&mut self, fields: Vec<String>) {
+        self.rows.push(fields);
+    }
+}
+
+/// Prints the full value for the structured formats (JSON and YAML), and the flat table for
+/// CSV and tables.
+pub fn print<T: Serialize>(format: OutputFormat, value: &T, table: Table) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => print!("{}", serde_norway::to_string(value)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            writer.write_record(&table.header)?;
+            for row in &table.rows {
+                writer.write_record(row)?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Table => print_table(&table),
+    }
+    Ok(())
+}
+
+fn print_table(table: &Table) {
+    let mut widths: Vec<usize> = table.header.iter().map(|h| h.len()).collect();
+    for row in &table.rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+
+    let header: Vec<String> = table.header.iter().map(|h| h.to_uppercase()).collect();
+    for row in std::iter::once(&header).chain(&table.rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(field, width)| format!("{:<width$}", field, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
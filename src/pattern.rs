@@ -0,0 +1,186 @@
+use crate::MacchangerError;
+use macaddr::MacAddr6;
+use std::{fmt, str::FromStr};
+
+/// A set of MAC addresses that generated addresses are constrained to, given either as a
+/// prefix (`02:aa:bb:00:00:00/24`) or as a template with `?` for every random hex digit
+/// (`00:1b:21:??:??:??`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacPattern {
+    /// The bits fixed by the pattern, zero everywhere else
+    value: u64,
+    /// Which bits are fixed by the pattern
+    mask: u64,
+}
+
+const ADDRESS_BITS: u32 = 48;
+const ADDRESS_MASK: u64 = (1 << ADDRESS_BITS) - 1;
+
+impl MacPattern {
+    /// The pattern matching the addresses starting with the `prefix_len` most significant
+    /// bits of `prefix`.
+    pub fn prefix(prefix: MacAddr6, prefix_len: u32) -> Result<Self, MacchangerError> {
+        if prefix_len > ADDRESS_BITS {
+            return Err(MacchangerError::InvalidMacPattern(format!(
+                "prefix length {} is longer than {} bits",
+                prefix_len, ADDRESS_BITS
+            )));
+        }
+        let mask = ADDRESS_MASK & !(ADDRESS_MASK >> prefix_len);
+        Ok(MacPattern {
+            value: to_bits(prefix) & mask,
+            mask,
+        })
+    }
+
+    /// The number of leading bits fixed by the pattern, if it is a prefix.
+    pub fn prefix_len(&self) -> Option<u32> {
+        let len = (self.mask << (64 - ADDRESS_BITS)).leading_ones();
+        (MacPattern::prefix(from_bits(self.value), len).ok()? == *self).then_some(len)
+    }
+
+    /// Replaces the bits of the address that are fixed by the pattern.
+    pub fn apply(&self, mac: MacAddr6) -> MacAddr6 {
+        from_bits((to_bits(mac) & !self.mask) | self.value)
+    }
+}
+
+impl FromStr for MacPattern {
+    type Err = MacchangerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MacchangerError::InvalidMacPattern(s.to_owned());
+        if let Some((prefix, prefix_len)) = s.split_once('/') {
+            let prefix = MacAddr6::from_str(prefix).map_err(|_| invalid())?;
+            let prefix_len = prefix_len.parse().map_err(|_| invalid())?;
+            return MacPattern::prefix(prefix, prefix_len);
+        }
+
+        let bytes: Vec<&str> = s.split([':', '-']).collect();
+        if bytes.len() != 6 || bytes.iter().any(|byte| byte.len() != 2) {
+            return Err(invalid());
+        }
+        let (mut value, mut mask) = (0, 0);
+        for digit in bytes.concat().chars() {
+            value <<= 4;
+            mask <<= 4;
+            if digit != '?' {
+                value |= u64::from(digit.to_digit(16).ok_or_else(invalid)?);
+                mask |= 0xF;
+            }
+        }
+        Ok(MacPattern { value, mask })
+    }
+}
+
+impl fmt::Display for MacPattern {
+    /// Shows prefixes as `02:AA:BB:00:00:00/24` and other patterns as templates.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(prefix_len) = self.prefix_len() {
+            return write!(f, "{}/{}", from_bits(self.value), prefix_len);
+        }
+        for i in (0..ADDRESS_BITS / 4).rev() {
+            let digit = (self.value >> (i * 4)) & 0xF;
+            if self.mask >> (i * 4) & 0xF == 0xF {
+                write!(f, "{:X}", digit)?;
+            } else {
+                // Partially fixed digits can't be shown in a template
+                f.write_str("?")?;
+            }
+            if i % 2 == 0 && i > 0 {
+                f.write_str(":")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_bits(mac: MacAddr6) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes[2..].copy_from_slice(mac.as_bytes());
+    u64::from_be_bytes(bytes)
+}
+
+fn from_bits(bits: u64) -> MacAddr6 {
+    let mut mac = [0u8; 6];
+    mac.copy_from_slice(&bits.to_be_bytes()[2..]);
+    MacAddr6::from(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mac(s: &str) -> MacAddr6 {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_prefixes() {
+        let pattern: MacPattern = "02:aa:bb:cc:dd:ee/24".parse().unwrap();
+        assert_eq!(pattern.prefix_len(), Some(24));
+        assert_eq!(pattern.to_string(), "02:AA:BB:00:00:00/24");
+
+        let pattern: MacPattern = "02:aa:bf:00:00:00/20".parse().unwrap();
+        assert_eq!(pattern.prefix_len(), Some(20));
+        assert_eq!(pattern.to_string(), "02:AA:B0:00:00:00/20");
+
+        let pattern: MacPattern = "02:00:00:00:00:01/48".parse().unwrap();
+        assert_eq!(pattern.to_string(), "02:00:00:00:00:01/48");
+        let pattern: MacPattern = "02:00:00:00:00:01/0".parse().unwrap();
+        assert_eq!(pattern.to_string(), "00:00:00:00:00:00/0");
+    }
+
+    #[test]
+    fn parses_templates() {
+        let pattern: MacPattern = "00:1b:21:??:??:??".parse().unwrap();
+        // A template that only fixes leading digits is a prefix
+        assert_eq!(pattern.prefix_len(), Some(24));
+        assert_eq!(pattern.to_string(), "00:1B:21:00:00:00/24");
+
+        let pattern: MacPattern = "?2-aa-??-??-??-0?".parse().unwrap();
+        assert_eq!(pattern.prefix_len(), None);
+        assert_eq!(pattern.to_string(), "?2:AA:??:??:??:0?");
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        for pattern in [
+            "",
+            "02:aa:bb:00:00:00/49",
+            "02:aa:bb:00:00:00/x",
+            "02:aa:bb:00:00/24",
+            "00:1b:21:??:??",
+            "00:1b:21:??:??:??:??",
+            "00:1b:21:?:??:???",
+            "00:1b:21:g?:??:??",
+        ] {
+            assert!(
+                matches!(
+                    pattern.parse::<MacPattern>(),
+                    Err(MacchangerError::InvalidMacPattern(_))
+                ),
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn applies_fixed_bits() {
+        let pattern: MacPattern = "02:aa:bb:00:00:00/20".parse().unwrap();
+        assert_eq!(
+            pattern.apply(mac("ff:ff:ff:ff:ff:ff")),
+            mac("02:aa:bf:ff:ff:ff")
+        );
+        let pattern: MacPattern = "?2:aa:??:??:??:0?".parse().unwrap();
+        assert_eq!(
+            pattern.apply(mac("ff:ff:ff:ff:ff:ff")),
+            mac("f2:aa:ff:ff:ff:0f")
+        );
+        assert_eq!(
+            pattern.apply(mac("00:00:00:00:00:00")),
+            mac("02:aa:00:00:00:00")
+        );
+    }
+}
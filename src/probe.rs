@@ -0,0 +1,250 @@
+use crate::os::LinuxMacchangerError;
+use nix::{
+    errno::Errno,
+    libc::{self, ETH_P_ALL, ETH_P_ARP, ETH_P_IP},
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+    sys::socket::{socket, AddressFamily, SockFlag, SockType},
+};
+use std::{
+    mem,
+    net::Ipv4Addr,
+    os::fd::{AsFd, AsRawFd, OwnedFd},
+    time::{Duration, Instant},
+};
+
+// Modeled after RFC 5227, but with shorter delays since a user is waiting for the change
+const PROBE_NUM: u32 = 3;
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+const PROBE_LISTEN: Duration = Duration::from_secs(1);
+
+// Not (yet) exported by the libc crate, see include/uapi/linux/if_packet.h
+const PACKET_OUTGOING: u8 = 4;
+
+const ETH_HLEN: usize = 14;
+const ARP_LEN: usize = 28;
+const ARPHRD_ETHER: u16 = 1;
+const ARPOP_REQUEST: u16 = 1;
+const BROADCAST: [u8; 6] = [0xFF; 6];
+
+/// An `AF_PACKET` socket bound to a single interface, used to probe whether a MAC address is
+/// in use by another host on the link.
+pub struct ArpProber {
+    fd: OwnedFd,
+    index: u32,
+}
+
+impl ArpProber {
+    pub fn open(index: u32) -> Result<Self, LinuxMacchangerError> {
+        // The protocol is only set when binding, so no frames of other interfaces are queued
+        let fd = socket(
+            AddressFamily::Packet,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            None,
+        )
+        .map_err(LinuxMacchangerError::Probe)?;
+        let address = link_address(index, ETH_P_ALL as u16, [0; 6]);
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        Errno::result(res).map_err(LinuxMacchangerError::Probe)?;
+        Ok(ArpProber { fd, index })
+    }
+
+    /// Sends ARP probes with `mac` as the sender hardware address for each of the targets and
+    /// returns whether any other host sent a frame from `mac` before the probing finished.
+    ///
+    /// A host using `mac` answers the probe for its own IPv4 address, from `mac`. Hosts that
+    /// own none of the targets are only found if they happen to send something while we
+    /// listen, so a silent host (or one without IPv4) can go unnoticed.
+    pub fn probe(&self, mac: [u8; 6], targets: &[Ipv4Addr]) -> Result<bool, LinuxMacchangerError> {
+        // The answers are addressed to `mac`, which the NIC would otherwise filter out. The
+        // kernel drops the filter entry again when the socket is closed.
+        self.add_unicast_membership(mac)?;
+        let probes: Vec<Vec<u8>> = targets
+            .iter()
+            .map(|target| arp_probe(mac, *target))
+            .collect();
+        for _ in 0..PROBE_NUM {
+            for probe in &probes {
+                self.send(probe)?;
+            }
+            if self.listen(mac, PROBE_INTERVAL)? {
+                return Ok(true);
+            }
+        }
+        self.listen(mac, PROBE_LISTEN)
+    }
+
+    fn add_unicast_membership(&self, mac: [u8; 6]) -> Result<(), LinuxMacchangerError> {
+        let mut membership: libc::packet_mreq = unsafe { mem::zeroed() };
+        membership.mr_ifindex = self.index as i32;
+        membership.mr_type = libc::PACKET_MR_UNICAST as u16;
+        membership.mr_alen = 6;
+        membership.mr_address[..6].copy_from_slice(&mac);
+        let res = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_PACKET,
+                libc::PACKET_ADD_MEMBERSHIP,
+                &membership as *const libc::packet_mreq as *const libc::c_void,
+                mem::size_of::<libc::packet_mreq>() as libc::socklen_t,
+            )
+        };
+        Errno::result(res)
+            .map(|_| ())
+            .map_err(LinuxMacchangerError::Probe)
+    }
+
+    fn send(&self, frame: &[u8]) -> Result<(), LinuxMacchangerError> {
+        let address = link_address(self.index, ETH_P_ARP as u16, BROADCAST);
+        let res = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                frame.as_ptr() as *const libc::c_void,
+                frame.len(),
+                0,
+                &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        Errno::result(res)
+            .map(|_| ())
+            .map_err(LinuxMacchangerError::Probe)
+    }
+
+    /// Waits for a frame from `mac`, either as the Ethernet source or as the sender of an
+    /// ARP packet, until the timeout expires.
+    fn listen(&self, mac: [u8; 6], timeout: Duration) -> Result<bool, LinuxMacchangerError> {
+        let deadline = Instant::now() + timeout;
+        let mut frame = [0u8; 2048];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+            let mut fds = [PollFd::new(self.fd.as_fd(), PollFlags::POLLIN)];
+            let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+            match poll(&mut fds, timeout) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => {}
+                Err(e) => return Err(LinuxMacchangerError::Probe(e)),
+            }
+
+            let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+            let mut address_len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            let res = unsafe {
+                libc::recvfrom(
+                    self.fd.as_raw_fd(),
+                    frame.as_mut_ptr() as *mut libc::c_void,
+                    frame.len(),
+                    0,
+                    &mut address as *mut libc::sockaddr_ll as *mut libc::sockaddr,
+                    &mut address_len,
+                )
+            };
+            let length = Errno::result(res).map_err(LinuxMacchangerError::Probe)? as usize;
+            // Our own probes are seen as outgoing frames
+            if address.sll_pkttype != PACKET_OUTGOING && is_from(&frame[..length], mac) {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+fn is_from(frame: &[u8], mac: [u8; 6]) -> bool {
+    if frame.get(6..12) == Some(&mac) {
+        return true;
+    }
+    let is_arp = frame.get(12..14) == Some(&(ETH_P_ARP as u16).to_be_bytes());
+    is_arp && frame.get(ETH_HLEN + 8..ETH_HLEN + 14) == Some(&mac)
+}
+
+/// An Ethernet frame with an ARP probe: a request for `target` with `mac` as the sender
+/// hardware address and an all-zero sender protocol address.
+fn arp_probe(mac: [u8; 6], target: Ipv4Addr) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(ETH_HLEN + ARP_LEN);
+    frame.extend_from_slice(&BROADCAST);
+    frame.extend_from_slice(&mac);
+    frame.extend_from_slice(&(ETH_P_ARP as u16).to_be_bytes());
+
+    frame.extend_from_slice(&ARPHRD_ETHER.to_be_bytes());
+    frame.extend_from_slice(&(ETH_P_IP as u16).to_be_bytes());
+    frame.push(6);
+    frame.push(4);
+    frame.extend_from_slice(&ARPOP_REQUEST.to_be_bytes());
+    frame.extend_from_slice(&mac);
+    frame.extend_from_slice(&Ipv4Addr::UNSPECIFIED.octets());
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&target.octets());
+    frame
+}
+
+fn link_address(index: u32, protocol: u16, address: [u8; 6]) -> libc::sockaddr_ll {
+    let mut link_address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    link_address.sll_family = libc::AF_PACKET as u16;
+    link_address.sll_protocol = protocol.to_be();
+    link_address.sll_ifindex = index as i32;
+    link_address.sll_halen = 6;
+    link_address.sll_addr[..6].copy_from_slice(&address);
+    link_address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0xBB];
+    const OTHER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0xCC];
+
+    #[test]
+    fn builds_arp_probe() {
+        let frame = arp_probe(MAC, Ipv4Addr::new(10, 77, 0, 2));
+        let expected = [
+            // Ethernet: broadcast destination, the candidate as source, ARP
+            &[0xFF; 6][..],
+            &MAC,
+            &[0x08, 0x06],
+            // An Ethernet/IPv4 request
+            &[0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x01],
+            // Sender: the candidate, without an IPv4 address
+            &MAC,
+            &[0, 0, 0, 0],
+            // Target: an unknown hardware address and the probed IPv4 address
+            &[0; 6],
+            &[10, 77, 0, 2],
+        ]
+        .concat();
+        assert_eq!(frame, expected);
+        assert_eq!(frame.len(), ETH_HLEN + ARP_LEN);
+    }
+
+    #[test]
+    fn recognizes_frames_from_mac() {
+        let arp_reply = |ethernet_source: [u8; 6], sender: [u8; 6]| {
+            let mut frame = arp_probe(sender, Ipv4Addr::new(10, 77, 0, 1));
+            frame[6..12].copy_from_slice(&ethernet_source);
+            frame[ETH_HLEN + 7] = 2;
+            frame
+        };
+        assert!(is_from(&arp_reply(MAC, MAC), MAC));
+        // Only one of the two has to match, e.g. for a proxy answering for the host
+        assert!(is_from(&arp_reply(OTHER_MAC, MAC), MAC));
+        assert!(is_from(&arp_reply(MAC, OTHER_MAC), MAC));
+        assert!(!is_from(&arp_reply(OTHER_MAC, OTHER_MAC), MAC));
+
+        let mut ipv4 = arp_reply(OTHER_MAC, OTHER_MAC);
+        ipv4[12..14].copy_from_slice(&(ETH_P_IP as u16).to_be_bytes());
+        ipv4[ETH_HLEN + 8..ETH_HLEN + 14].copy_from_slice(&MAC);
+        // Outside of ARP packets these bytes are no sender address
+        assert!(!is_from(&ipv4, MAC));
+
+        assert!(!is_from(&[], MAC));
+        assert!(is_from(&arp_reply(MAC, OTHER_MAC)[..12], MAC));
+        assert!(!is_from(&arp_reply(OTHER_MAC, MAC)[..ETH_HLEN + 10], MAC));
+    }
+}
@@ -1,13 +1,58 @@
+use crate::{MacGenerator, OsGenerator, SlapQuadrant};
 use macaddr::MacAddr;
-use rand::Rng;
 
+/// Generates a random locally administered unicast MAC address. Only the multicast and
+/// local bits are fixed, the other 46 bits are random.
 pub fn generate_random_mac() -> MacAddr {
-    let mut rng = rand::thread_rng();
-    let mut mac = [0u8; 6];
+    OsGenerator.random_mac()
+}
+
+/// Generates a random locally administered unicast MAC address in the given SLAP quadrant.
+/// The quadrant fixes the low four bits of the first byte, the other 44 bits are random.
+pub fn generate_random_mac_in_quadrant(quadrant: SlapQuadrant) -> MacAddr {
+    OsGenerator.random_mac_in_quadrant(quadrant)
+}
 
-    rng.fill(&mut mac);
+/// Generates a random MAC address that keeps the given OUI (the first three bytes)
+/// and only randomizes the NIC-specific lower three bytes.
+pub fn generate_random_mac_with_oui(oui: [u8; 3]) -> MacAddr {
+    OsGenerator.random_mac_with_oui(oui)
+}
 
-    mac[0] = 0x02;
+/// Generates a random MAC address inside the block identified by the `prefix_bits` most
+/// significant bits of `prefix` (given right-aligned, e.g. `0x001B21` with 24 bits).
+///
+/// # Panics
+///
+/// Panics if `prefix_bits` is larger than 48.
+pub fn generate_random_mac_in_block(prefix: u64, prefix_bits: u32) -> MacAddr {
+    OsGenerator.random_mac_in_block(prefix, prefix_bits)
+}
+
+/// Computes the Levenshtein edit distance between two strings.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
 
-    MacAddr::from(mac)
+/// Returns the names that are close to `name`, ordered from most to least similar.
+pub fn similar_names<'a>(name: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut candidates: Vec<(usize, &str)> = names
+        .into_iter()
+        .map(|n| (edit_distance(name, n), n))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates.into_iter().map(|(_, n)| n.to_owned()).collect()
 }
@@ -1,6 +1,16 @@
+use crate::util::similar_names;
 use crate::Adapter;
+use crate::AdapterKind;
+use crate::AddressOrigin;
+use crate::ChangeOptions;
+use crate::HardwareAddr;
+use crate::HardwareMac;
 use crate::Interface;
+use crate::LinkDownPolicy;
+use crate::LinkType;
+use crate::MacSource;
 use crate::MacchangerError;
+use crate::OperState;
 use macaddr::MacAddr;
 use std::{borrow::BorrowMut, fmt::Debug, ptr, str::FromStr};
 
@@ -23,8 +33,17 @@ use windows::{
 impl From<WindowsAdapter> for Interface {
     fn from(value: WindowsAdapter) -> Self {
         Self {
+            index: value.index,
+            flags: 0,
+            operstate: value.operstate,
+            carrier: None,
+            mtu: value.mtu,
+            adapter: value.clone().into(),
             name: value.name,
-            mac: value.mac_address,
+            mac: value.mac_address.into(),
+            origin: AddressOrigin::Unknown,
+            permanent_mac: None,
+            warnings: vec![],
         }
     }
 }
@@ -33,14 +52,38 @@ impl From<WindowsAdapter> for Adapter {
     fn from(value: WindowsAdapter) -> Self {
         Self {
             name: value.description,
+            kind: value.kind,
+            driver: None,
+            bus_path: None,
+            ids: None,
+            warnings: vec![],
         }
     }
 }
 
-use IpHelper::{GetAdaptersAddresses, GAA_FLAG_INCLUDE_ALL_INTERFACES, IP_ADAPTER_ADDRESSES_LH};
+use IpHelper::{
+    FreeMibTable, GetAdaptersAddresses, GetIpNetTable2, GAA_FLAG_INCLUDE_ALL_INTERFACES,
+    IP_ADAPTER_ADDRESSES_LH, MIB_IPNET_TABLE2,
+};
 use WindowsFirewall::{IEnumNetConnection, INetConnection, INetConnectionManager, NCME_DEFAULT};
 
-pub fn change_mac(mac: MacAddr, interface: String) -> Result<MacAddr, MacchangerError> {
+pub fn change_mac(
+    address: &HardwareAddr,
+    interface: String,
+    options: &ChangeOptions,
+) -> Result<HardwareAddr, MacchangerError> {
+    // The NetworkAddress registry value only holds MAC addresses
+    let mac = address
+        .to_mac()
+        .ok_or_else(|| MacchangerError::NotAMacAddress(address.clone()))?;
+    // There is no raw socket access to send ARP probes from another address
+    if options.probe {
+        return Err(MacchangerError::UnsupportedPlatform);
+    }
+    // The adapter always has to be disabled for the registry value to take effect
+    if options.link_down == LinkDownPolicy::Never {
+        return Err(MacchangerError::UnsupportedPlatform);
+    }
     let adapter = get_adapter(interface)?;
     let adapter_registry_key = get_registry_key(&adapter)?;
 
@@ -56,14 +99,28 @@ pub fn change_mac(mac: MacAddr, interface: String) -> Result<MacAddr, Macchanger
 
     if res == ERROR_SUCCESS {
         change_adapter_connection_status(&adapter, false)?;
-        change_adapter_connection_status(&adapter, true)?;
+        if !options.keep_down {
+            change_adapter_connection_status(&adapter, true)?;
+        }
     } else {
         return Err(MacchangerError::ConnectionResetError);
     }
-    Ok(mac)
+
+    // The new address only shows up once the adapter is enabled again
+    if options.keep_down {
+        return Ok(mac.into());
+    }
+    let actual = get_adapter(adapter.name)?.mac_address;
+    if actual != mac {
+        return Err(MacchangerError::VerificationFailed {
+            requested: mac.into(),
+            actual: actual.into(),
+        });
+    }
+    Ok(actual.into())
 }
 
-pub fn get_hardware_mac(interface: String) -> Result<MacAddr, MacchangerError> {
+pub fn get_hardware_mac(interface: String) -> Result<HardwareMac, MacchangerError> {
     let adapter = get_adapter(interface)?;
     let adapter_registry_key = get_registry_key(&adapter)?;
 
@@ -89,7 +146,10 @@ pub fn get_hardware_mac(interface: String) -> Result<MacAddr, MacchangerError> {
 
     let original_mac: MacAddr =
         MacAddr::from_str(value).map_err(|_| MacchangerError::StringConversionError)?;
-    Ok(original_mac)
+    Ok(HardwareMac {
+        mac: original_mac.into(),
+        source: MacSource::Registry,
+    })
 }
 
 fn change_adapter_connection_status(
@@ -302,6 +362,52 @@ pub struct WindowsAdapter {
     pub description: String,
     pub mac_address: MacAddr,
     pub instance_id: String,
+    pub kind: AdapterKind,
+    pub index: u32,
+    pub mtu: u32,
+    pub operstate: OperState,
+}
+
+/// Reads the details of one entry of the `GetAdaptersAddresses` list.
+impl TryFrom<&IP_ADAPTER_ADDRESSES_LH> for WindowsAdapter {
+    type Error = MacchangerError;
+
+    fn try_from(adapter: &IP_ADAPTER_ADDRESSES_LH) -> Result<Self, Self::Error> {
+        let name = unsafe { adapter.FriendlyName.to_string() }
+            .map_err(|_| MacchangerError::AdapterError)?;
+        let description = unsafe { adapter.Description.to_string() }
+            .map_err(|_| MacchangerError::AdapterError)?;
+        let instance_id = unsafe { adapter.AdapterName.to_string() }
+            .map_err(|_| MacchangerError::AdapterError)?;
+
+        let mac_bytes: [u8; 6] = adapter.PhysicalAddress[..6]
+            .try_into()
+            .map_err(|_| MacchangerError::AdapterError)?;
+        let kind = match adapter.IfType {
+            IpHelper::IF_TYPE_IEEE80211 => AdapterKind::Wireless,
+            IpHelper::IF_TYPE_ETHERNET_CSMACD => AdapterKind::Wired,
+            _ => AdapterKind::Virtual,
+        };
+        let operstate = match adapter.OperStatus {
+            Ndis::IfOperStatusUp => OperState::Up,
+            Ndis::IfOperStatusDown => OperState::Down,
+            Ndis::IfOperStatusDormant => OperState::Dormant,
+            Ndis::IfOperStatusLowerLayerDown => OperState::LowerLayerDown,
+            Ndis::IfOperStatusNotPresent => OperState::NotPresent,
+            Ndis::IfOperStatusTesting => OperState::Testing,
+            _ => OperState::Unknown,
+        };
+        Ok(WindowsAdapter {
+            name,
+            description,
+            mac_address: MacAddr::from(mac_bytes),
+            instance_id,
+            kind,
+            index: unsafe { adapter.Anonymous1.Anonymous.IfIndex },
+            mtu: adapter.Mtu,
+            operstate,
+        })
+    }
 }
 
 fn get_adapter(interface: String) -> Result<WindowsAdapter, MacchangerError> {
@@ -311,7 +417,18 @@ fn get_adapter(interface: String) -> Result<WindowsAdapter, MacchangerError> {
         .iter()
         .find(|a| a.name == interface)
         .cloned()
-        .ok_or(MacchangerError::Generic)
+        .ok_or_else(|| MacchangerError::InterfaceNotFound {
+            candidates: similar_names(&interface, adapters.iter().map(|a| a.name.as_str())),
+            name: interface,
+        })
+}
+
+pub fn get_interface(interface: String) -> Result<WindowsAdapter, MacchangerError> {
+    get_adapter(interface)
+}
+
+pub fn get_adapter_kind(interface: String) -> Result<AdapterKind, MacchangerError> {
+    Ok(get_adapter(interface)?.kind)
 }
 
 pub fn list_adapters() -> Result<Vec<WindowsAdapter>, MacchangerError> {
@@ -323,38 +440,7 @@ pub fn list_adapters() -> Result<Vec<WindowsAdapter>, MacchangerError> {
             break;
         }
 
-        let adapter_name = unsafe {
-            (*adapter_list)
-                .FriendlyName
-                .to_string()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-        let adapter_description = unsafe {
-            (*adapter_list)
-                .Description
-                .to_string()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-
-        let adapter_instance_id = unsafe {
-            (*adapter_list)
-                .AdapterName
-                .to_string()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-
-        let mac_bytes: [u8; 6] = unsafe {
-            (*adapter_list).PhysicalAddress[..6]
-                .try_into()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-        let mac = MacAddr::from(mac_bytes);
-        adapters.push(WindowsAdapter {
-            name: adapter_name,
-            description: adapter_description,
-            mac_address: mac,
-            instance_id: adapter_instance_id,
-        });
+        adapters.push(WindowsAdapter::try_from(unsafe { &*adapter_list })?);
 
         adapter_list = unsafe { (*adapter_list).Next };
     }
@@ -375,6 +461,33 @@ pub fn list_adapters() -> Result<Vec<WindowsAdapter>, MacchangerError> {
     Ok(adapters)
 }
 
+/// The link-layer addresses of the neighbors in the ARP and NDP caches.
+pub fn list_neighbor_addresses() -> Result<Vec<HardwareAddr>, MacchangerError> {
+    let mut table: *mut MIB_IPNET_TABLE2 = ptr::null_mut();
+    let res = unsafe { GetIpNetTable2(AF_UNSPEC, &mut table) };
+    if res != ERROR_SUCCESS {
+        return Err(MacchangerError::AdapterError);
+    }
+
+    let rows = unsafe {
+        std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize)
+    };
+    let addresses = rows
+        .iter()
+        // Unreachable entries may still hold the address of a neighbor that has since gone away
+        .filter(|row| row.State != NlnsUnreachable)
+        .filter_map(|row| {
+            let address = row
+                .PhysicalAddress
+                .get(..row.PhysicalAddressLength as usize)?;
+            HardwareAddr::new(LinkType::from_address_len(address.len()), address).ok()
+        })
+        .collect();
+
+    unsafe { FreeMibTable(table as *const core::ffi::c_void) };
+    Ok(addresses)
+}
+
 pub fn list_interfaces() -> Result<Vec<WindowsAdapter>, MacchangerError> {
     let (mut adapter_list, adapter_count) = get_raw_adapters()?;
     let mut adapters: Vec<WindowsAdapter> = vec![];
@@ -384,38 +497,7 @@ pub fn list_interfaces() -> Result<Vec<WindowsAdapter>, MacchangerError> {
             break;
         }
 
-        let adapter_name = unsafe {
-            (*adapter_list)
-                .FriendlyName
-                .to_string()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-        let adapter_description = unsafe {
-            (*adapter_list)
-                .Description
-                .to_string()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-
-        let adapter_instance_id = unsafe {
-            (*adapter_list)
-                .AdapterName
-                .to_string()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-
-        let mac_bytes: [u8; 6] = unsafe {
-            (*adapter_list).PhysicalAddress[..6]
-                .try_into()
-                .map_err(|_| MacchangerError::AdapterError)?
-        };
-        let mac = MacAddr::from(mac_bytes);
-        adapters.push(WindowsAdapter {
-            name: adapter_name,
-            description: adapter_description,
-            mac_address: mac,
-            instance_id: adapter_instance_id,
-        });
+        adapters.push(WindowsAdapter::try_from(unsafe { &*adapter_list })?);
 
         adapter_list = unsafe { (*adapter_list).Next };
     }
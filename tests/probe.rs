@@ -0,0 +1,131 @@
+//! Probes a veth pair whose peer lives in another network namespace. This needs root and
+//! iproute2, so it only runs on request: `cargo test --test probe -- --ignored`.
+#![cfg(all(target_os = "linux", feature = "cli"))]
+
+use std::{
+    env,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+const PEER_MAC: &str = "02:00:00:00:00:bb";
+
+/// A pair of network namespaces connected by the veth pair `va`/`vb`, removed when dropped.
+struct Link {
+    local: String,
+    peer: String,
+    state_dir: PathBuf,
+}
+
+impl Link {
+    fn new(name: &str) -> Self {
+        let id = std::process::id();
+        let link = Link {
+            local: format!("mc-{}-a-{}", name, id),
+            peer: format!("mc-{}-b-{}", name, id),
+            state_dir: env::temp_dir().join(format!("mc-{}-{}", name, id)),
+        };
+        std::fs::create_dir_all(&link.state_dir).unwrap();
+        ip(&["netns", "add", &link.local]);
+        ip(&["netns", "add", &link.peer]);
+        link.local(&["link", "add", "va", "type", "veth", "peer", "name", "vb"]);
+        link.local(&["link", "set", "vb", "netns", &link.peer]);
+        link.peer(&["link", "set", "vb", "address", PEER_MAC]);
+        // Without IPv6 neither end sends anything by itself
+        for namespace in [&link.local, &link.peer] {
+            ip(&[
+                "netns",
+                "exec",
+                namespace,
+                "sysctl",
+                "-qw",
+                "net.ipv6.conf.all.disable_ipv6=1",
+            ]);
+        }
+        link.local(&["link", "set", "va", "up"]);
+        link.peer(&["link", "set", "vb", "up"]);
+        link
+    }
+
+    fn local(&self, args: &[&str]) {
+        ip(&[&["-n", self.local.as_str()], args].concat());
+    }
+
+    fn peer(&self, args: &[&str]) {
+        ip(&[&["-n", self.peer.as_str()], args].concat());
+    }
+
+    fn change(&self, mac: &str) -> Output {
+        Command::new("ip")
+            .args(["netns", "exec", &self.local])
+            .arg(env!("CARGO_BIN_EXE_macchanger"))
+            .args(["change", "va", mac, "--probe"])
+            .env("MACCHANGER_STATE_DIR", &self.state_dir)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        for namespace in [&self.local, &self.peer] {
+            let _ = Command::new("ip")
+                .args(["netns", "del", namespace])
+                .status();
+        }
+        let _ = std::fs::remove_dir_all(&self.state_dir);
+    }
+}
+
+fn ip(args: &[&str]) {
+    let status = Command::new("ip").args(args).status().unwrap();
+    assert!(status.success(), "ip {} failed", args.join(" "));
+}
+
+fn assert_in_use(output: &Output) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("already in use"), "{}", stderr);
+}
+
+#[test]
+#[ignore = "needs root and iproute2"]
+fn finds_gateway_using_mac() {
+    let link = Link::new("gw");
+    link.local(&["address", "add", "10.77.0.1/24", "dev", "va"]);
+    link.peer(&["address", "add", "10.77.0.2/24", "dev", "vb"]);
+    link.local(&["route", "add", "default", "via", "10.77.0.2"]);
+
+    assert_in_use(&link.change(PEER_MAC));
+    assert!(link.change("02:00:00:00:00:cc").status.success());
+}
+
+#[test]
+#[ignore = "needs root and iproute2"]
+fn finds_neighbor_using_mac() {
+    let link = Link::new("nb");
+    link.local(&["address", "add", "10.77.0.1/24", "dev", "va"]);
+    link.peer(&["address", "add", "10.77.0.2/24", "dev", "vb"]);
+    // A stale entry from before the peer changed its address
+    link.local(&[
+        "neigh",
+        "replace",
+        "10.77.0.2",
+        "dev",
+        "va",
+        "lladdr",
+        "02:00:00:00:00:99",
+        "nud",
+        "stale",
+    ]);
+
+    assert_in_use(&link.change(PEER_MAC));
+}
+
+#[test]
+#[ignore = "needs root and iproute2"]
+fn misses_silent_host() {
+    // Without any IPv4 address to ask for, a host that sends nothing can't be found
+    let link = Link::new("sl");
+    assert!(link.change(PEER_MAC).status.success());
+}